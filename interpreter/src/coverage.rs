@@ -0,0 +1,346 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+    ops::Deref,
+    rc::Rc,
+};
+
+use crate::{
+    env::Env,
+    error::ResultMSG,
+    expr::{Expr, Visitor as ExprVisitor},
+    function::Signature,
+    hooks::Hooks,
+    object::Object,
+    stmt::{Stmt, Visitor as StmtVisitor},
+    token::{Token, TokenType},
+};
+
+/// A condition and how often it held and failed.
+pub struct Branch {
+    pub line: u32,
+    pub held: u64,
+    pub failed: u64,
+}
+
+/// A function declaration and how often it was called.
+pub struct Function {
+    pub name: String,
+    pub line: u32,
+    pub calls: u64,
+}
+
+/// Which statements, conditions and functions of a program ran. The
+/// program is walked up front so what never ran is known too.
+pub struct Coverage {
+    /// Statements run per line, for every line with a statement.
+    pub lines: BTreeMap<u32, u64>,
+    /// In source order.
+    pub branches: Vec<Branch>,
+    pub functions: Vec<Function>,
+    /// Branches by the address of their condition, which stays put while
+    /// the program runs.
+    conditions: HashMap<*const Expr, usize>,
+    /// Functions by the address of their body, as names can repeat.
+    declared: HashMap<*const Stmt, usize>,
+}
+
+impl Coverage {
+    pub fn new(stmts: &[Stmt]) -> Self {
+        let mut coverage = Coverage {
+            lines: BTreeMap::new(),
+            branches: Vec::new(),
+            functions: Vec::new(),
+            conditions: HashMap::new(),
+            declared: HashMap::new(),
+        };
+        for stmt in stmts {
+            stmt.accept(&mut coverage);
+        }
+        coverage
+    }
+
+    fn condition(&mut self, cond: &Expr) {
+        self.conditions.insert(cond, self.branches.len());
+        self.branches.push(Branch {
+            line: cond.line(),
+            held: 0,
+            failed: 0,
+        });
+    }
+
+    /// The coverage in the lcov tracefile format, naming the script `path`.
+    pub fn lcov(&self, path: &str) -> String {
+        let mut out = format!("TN:\nSF:{}\n", path);
+
+        for f in &self.functions {
+            let _ = writeln!(out, "FN:{},{}", f.line, f.name);
+        }
+        for f in &self.functions {
+            let _ = writeln!(out, "FNDA:{},{}", f.calls, f.name);
+        }
+        let called = self.functions.iter().filter(|f| f.calls > 0).count();
+        let _ = writeln!(out, "FNF:{}\nFNH:{}", self.functions.len(), called);
+
+        for (i, b) in self.branches.iter().enumerate() {
+            for (n, taken) in [b.held, b.failed].into_iter().enumerate() {
+                match b.held + b.failed {
+                    0 => {
+                        let _ = writeln!(out, "BRDA:{},{},{},-", b.line, i, n);
+                    }
+                    _ => {
+                        let _ = writeln!(out, "BRDA:{},{},{},{}", b.line, i, n, taken);
+                    }
+                }
+            }
+        }
+        let (found, hit) = self.branches_hit();
+        let _ = writeln!(out, "BRF:{}\nBRH:{}", found, hit);
+
+        for (line, hits) in &self.lines {
+            let _ = writeln!(out, "DA:{},{}", line, hits);
+        }
+        let run = self.lines.values().filter(|hits| **hits > 0).count();
+        let _ = writeln!(out, "LF:{}\nLH:{}", self.lines.len(), run);
+
+        out.push_str("end_of_record\n");
+        out
+    }
+
+    /// A summary for the terminal: the share of lines, branches and
+    /// functions that ran, then what did not.
+    pub fn summary(&self, path: &str) -> String {
+        let run = self.lines.values().filter(|hits| **hits > 0).count();
+        let (found, hit) = self.branches_hit();
+        let called = self.functions.iter().filter(|f| f.calls > 0).count();
+
+        let mut out = format!("coverage of {}\n", path);
+        for (what, hit, found) in [
+            ("lines", run, self.lines.len()),
+            ("branches", hit, found),
+            ("functions", called, self.functions.len()),
+        ] {
+            let _ = writeln!(
+                out,
+                "  {:<10} {:>5}/{:<5} {}",
+                what,
+                hit,
+                found,
+                percent(hit, found)
+            );
+        }
+
+        let missed: Vec<u32> = self
+            .lines
+            .iter()
+            .filter(|(_, hits)| **hits == 0)
+            .map(|(line, _)| *line)
+            .collect();
+        if !missed.is_empty() {
+            let _ = writeln!(out, "  lines not run: {}", ranges(&missed));
+        }
+        for b in &self.branches {
+            let never = match (b.held, b.failed) {
+                (0, 0) => "never reached",
+                (0, _) => "never true",
+                (_, 0) => "never false",
+                _ => continue,
+            };
+            let _ = writeln!(out, "  condition at line {} {}", b.line, never);
+        }
+        for f in self.functions.iter().filter(|f| f.calls == 0) {
+            let _ = writeln!(out, "  function {} at line {} never called", f.name, f.line);
+        }
+        out
+    }
+
+    /// How many ways conditions could go, and how many they went.
+    fn branches_hit(&self) -> (usize, usize) {
+        let hit = self
+            .branches
+            .iter()
+            .map(|b| (b.held > 0) as usize + (b.failed > 0) as usize)
+            .sum();
+        (self.branches.len() * 2, hit)
+    }
+}
+
+fn percent(hit: usize, found: usize) -> String {
+    match found {
+        0 => "-".to_string(),
+        _ => format!("{:.1}%", hit as f64 * 100.0 / found as f64),
+    }
+}
+
+/// Sorted line numbers with runs joined, as in `3, 7-9`.
+fn ranges(lines: &[u32]) -> String {
+    let mut runs: Vec<(u32, u32)> = Vec::new();
+    for &line in lines {
+        match runs.last_mut() {
+            Some((_, end)) if *end + 1 == line => *end = line,
+            _ => runs.push((line, line)),
+        }
+    }
+    runs.iter()
+        .map(|&(start, end)| match start == end {
+            true => start.to_string(),
+            false => format!("{}-{}", start, end),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl Hooks for Coverage {
+    fn statement(&mut self, stmt: &Stmt, _env: &Rc<Env>) -> ResultMSG<()> {
+        if let Some(hits) = stmt.line().and_then(|line| self.lines.get_mut(&line)) {
+            *hits += 1;
+        }
+        Ok(())
+    }
+
+    fn branch(&mut self, cond: &Expr, held: bool) {
+        if let Some(&i) = self.conditions.get(&(cond as *const Expr)) {
+            match held {
+                true => self.branches[i].held += 1,
+                false => self.branches[i].failed += 1,
+            }
+        }
+    }
+
+    fn enter(&mut self, _name: &str, body: &Stmt, _args: &[(String, Object)]) {
+        if let Some(&i) = self.declared.get(&(body as *const Stmt)) {
+            self.functions[i].calls += 1;
+        }
+    }
+}
+
+impl ExprVisitor<()> for Coverage {
+    fn visit_expr(&mut self, expr: &Expr) {
+        match *expr {
+            Expr::Identifier(_) | Expr::Literal(_) | Expr::Update(..) => {}
+            Expr::Grouping(ref inside) => inside.accept(self),
+            Expr::Unary(_, ref rhs) => rhs.accept(self),
+            Expr::Binary(ref lhs, ref op, ref rhs) => {
+                lhs.accept(self);
+                if op.in_types(vec![
+                    TokenType::OR,
+                    TokenType::AND,
+                    TokenType::QUESTION_QUESTION,
+                ]) {
+                    self.condition(lhs);
+                }
+                rhs.accept(self);
+            }
+            Expr::Assignment(_, ref rhs) | Expr::Compound(_, _, ref rhs) => rhs.accept(self),
+            Expr::Call(ref callee, _, ref args, ref named) => {
+                callee.accept(self);
+                args.iter().for_each(|a| a.accept(self));
+                named.iter().for_each(|(_, a)| a.accept(self));
+            }
+            Expr::Index(ref object, _, ref index) => {
+                object.accept(self);
+                index.accept(self);
+            }
+            Expr::Interpolation(ref parts) => parts.iter().for_each(|p| p.accept(self)),
+            Expr::Conditional(ref c, ref t, ref e) => {
+                c.accept(self);
+                self.condition(c);
+                t.accept(self);
+                e.accept(self);
+            }
+            Expr::Get(ref object, _, _) => object.accept(self),
+        }
+    }
+}
+
+impl StmtVisitor<()> for Coverage {
+    fn visit_stmt(&mut self, s: &Stmt) {
+        // the lines the interpreter reports to the statement hook
+        match *s {
+            Stmt::Empty | Stmt::Block(_) | Stmt::Doc(..) => {}
+            _ => {
+                if let Some(line) = s.line() {
+                    self.lines.entry(line).or_insert(0);
+                }
+            }
+        }
+
+        match *s {
+            Stmt::Empty => {}
+            Stmt::Break(l) => self.visit_break(l),
+            Stmt::Print(ref e) => self.visit_print_stmt(e),
+            Stmt::Expression(ref e) => self.visit_expression_stmt(e),
+            Stmt::Block(ref ss) => self.visit_block_stmt(ss),
+            Stmt::Declaration(ref n, ref e) => self.visit_declaration_stmt(n, e.as_ref()),
+            Stmt::Const(ref n, ref e) => self.visit_const_stmt(n, e),
+            Stmt::If(ref c, ref t, ref e) => {
+                self.visit_if(c, t.as_ref(), e.as_ref().map(|x| x.deref()))
+            }
+            Stmt::While(ref e, ref b) => self.visit_while(e, b.deref()),
+            Stmt::Return(l, ref e) => self.visit_return(l, e),
+            Stmt::Function(ref n, ref s, ref b) => self.visit_function(n, s.clone(), b.clone()),
+            Stmt::Doc(_, ref inner) => self.visit_stmt(inner),
+        }
+    }
+
+    fn visit_block_stmt(&mut self, statements: &Vec<Stmt>) {
+        for stmt in statements {
+            stmt.accept(self);
+        }
+    }
+
+    fn visit_expression_stmt(&mut self, expression: &Expr) {
+        expression.accept(self)
+    }
+
+    fn visit_print_stmt(&mut self, expression: &Expr) {
+        expression.accept(self)
+    }
+
+    fn visit_declaration_stmt(&mut self, _name: &Token, init: Option<&Expr>) {
+        if let Some(init) = init {
+            init.accept(self);
+        }
+    }
+
+    fn visit_const_stmt(&mut self, _name: &Token, init: &Expr) {
+        init.accept(self);
+    }
+
+    fn visit_if(&mut self, expr: &Expr, then_stmt: &Stmt, else_stmt: Option<&Stmt>) {
+        expr.accept(self);
+        self.condition(expr);
+        then_stmt.accept(self);
+        if let Some(else_stmt) = else_stmt {
+            else_stmt.accept(self);
+        }
+    }
+
+    fn visit_while(&mut self, expr: &Expr, body: &Stmt) {
+        expr.accept(self);
+        self.condition(expr);
+        body.accept(self);
+    }
+
+    fn visit_break(&mut self, _line: u32) {}
+
+    fn visit_function(&mut self, name: &Token, signature: Rc<Signature>, body: Rc<Stmt>) {
+        self.declared
+            .insert(Rc::as_ptr(&body), self.functions.len());
+        self.functions.push(Function {
+            name: name.lexeme.clone(),
+            line: name.line,
+            calls: 0,
+        });
+        for param in &signature.params {
+            if let Some(ref default) = param.default {
+                default.accept(self);
+            }
+        }
+        body.accept(self);
+    }
+
+    fn visit_return(&mut self, _line: u64, expr: &Expr) {
+        expr.accept(self)
+    }
+}
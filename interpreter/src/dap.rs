@@ -0,0 +1,507 @@
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    io::{BufRead, Write},
+    rc::Rc,
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use crate::{
+    analyze,
+    debug::{self, detached, Session, Step},
+    env::Env,
+    error::ResultMSG,
+    exit_code,
+    hooks::Hooks,
+    json::Json,
+    lsp::{read_message, write_message},
+    object::Object,
+    read_source,
+    resolver::Resolver,
+    script_interpreter,
+    stmt::Stmt,
+    Options, EX_IOERR,
+};
+
+/// Requests that only make sense while the program is stopped. Any that
+/// arrive while it runs wait until it stops.
+const WHILE_STOPPED: &[&str] = &[
+    "stackTrace",
+    "scopes",
+    "variables",
+    "evaluate",
+    "continue",
+    "next",
+    "stepIn",
+    "stepOut",
+];
+
+/// What the adapter should do after a request.
+#[derive(PartialEq)]
+enum Next {
+    Wait,
+    Launch,
+    Resume,
+    Disconnect,
+}
+
+/// A debug adapter for the Debug Adapter Protocol. Requests are read on a
+/// separate thread so the running program can notice `pause` requests and
+/// breakpoint changes between statements.
+pub struct Adapter {
+    messages: Receiver<String>,
+    pending: VecDeque<String>,
+    output: Box<dyn Write>,
+    seq: u64,
+    program: Option<String>,
+    opts: Options,
+    session: Session,
+    /// Scopes handed to the client as variable references while stopped.
+    handles: Vec<Rc<Env>>,
+    running: bool,
+    stopped: bool,
+    disconnected: bool,
+}
+
+/// Serves one debugging session and returns once the client disconnects.
+pub fn serve(input: Box<dyn BufRead + Send>, output: Box<dyn Write>) -> i32 {
+    let (sender, messages) = mpsc::channel();
+    thread::spawn(move || {
+        let mut input = input;
+        while let Ok(Some(body)) = read_message(&mut *input) {
+            if sender.send(body).is_err() {
+                break;
+            }
+        }
+    });
+
+    let adapter = Rc::new(RefCell::new(Adapter {
+        messages,
+        pending: VecDeque::new(),
+        output,
+        seq: 0,
+        program: None,
+        opts: Options::default(),
+        session: Session::new(),
+        handles: Vec::new(),
+        running: false,
+        stopped: false,
+        disconnected: false,
+    }));
+
+    // configure until the client is ready for the program to start
+    loop {
+        match adapter.borrow_mut().next() {
+            Next::Launch => break,
+            Next::Disconnect => return 0,
+            _ => {}
+        }
+    }
+
+    let status = launch(&adapter);
+
+    let mut adapter = adapter.borrow_mut();
+    adapter.running = false;
+    if !adapter.disconnected {
+        adapter.event(
+            "exited",
+            Json::object(vec![("exitCode", Json::Number(status as f64))]),
+        );
+        adapter.event("terminated", Json::object(vec![]));
+        while adapter.next() != Next::Disconnect {}
+    }
+    status
+}
+
+/// Runs the launched program with the adapter watching it, returning the
+/// exit status it would have had on its own.
+fn launch(adapter: &Rc<RefCell<Adapter>>) -> i32 {
+    let path = adapter.borrow().program.clone().unwrap_or_default();
+    let code = match read_source(&path) {
+        Ok(code) => code,
+        Err(e) => {
+            adapter
+                .borrow_mut()
+                .output(&format!("cannot read {}: {}\n", path, e), "stderr");
+            return EX_IOERR;
+        }
+    };
+
+    let stmts = match analyze(&code, Resolver::new()) {
+        Ok(stmts) => stmts,
+        Err(errors) => {
+            for err in &errors {
+                adapter.borrow_mut().output(&format!("{}\n", err), "stderr");
+            }
+            return exit_code(&errors[0]);
+        }
+    };
+
+    let interpreter = {
+        let mut state = adapter.borrow_mut();
+        let interpreter = script_interpreter(&state.opts);
+        state.session.start(interpreter.env.clone());
+        state.running = true;
+        interpreter
+    };
+    let mut interpreter = interpreter.hooks(adapter.clone());
+
+    for stmt in &stmts {
+        if let Err(e) = interpreter.interpret(stmt) {
+            let mut adapter = adapter.borrow_mut();
+            if adapter.disconnected {
+                return 0;
+            }
+            adapter.output(&format!("{}\n", e), "stderr");
+            return exit_code(&e);
+        }
+    }
+    0
+}
+
+impl Adapter {
+    fn send(&mut self, mut msg: Vec<(&str, Json)>) {
+        if self.disconnected {
+            return;
+        }
+        self.seq += 1;
+        msg.insert(0, ("seq", Json::Number(self.seq as f64)));
+        let _ = write_message(&mut *self.output, &Json::object(msg));
+    }
+
+    fn event(&mut self, event: &str, body: Json) {
+        self.send(vec![
+            ("type", Json::str("event")),
+            ("event", Json::str(event)),
+            ("body", body),
+        ]);
+    }
+
+    fn respond(&mut self, request: &Json, result: Result<Json, String>) {
+        let mut msg = vec![
+            ("type", Json::str("response")),
+            (
+                "request_seq",
+                request.get("seq").cloned().unwrap_or(Json::Null),
+            ),
+            ("success", Json::Bool(result.is_ok())),
+            (
+                "command",
+                request.get("command").cloned().unwrap_or(Json::Null),
+            ),
+        ];
+        match result {
+            Ok(body) => msg.push(("body", body)),
+            Err(message) => msg.push(("message", Json::String(message))),
+        }
+        self.send(msg);
+    }
+
+    fn output(&mut self, text: &str, category: &str) {
+        self.event(
+            "output",
+            Json::object(vec![
+                ("category", Json::str(category)),
+                ("output", Json::str(text)),
+            ]),
+        );
+    }
+
+    /// Handles the next request, waiting for one if there is none. A closed
+    /// input counts as a disconnect.
+    fn next(&mut self) -> Next {
+        let body = match self.pending.pop_front() {
+            Some(body) => body,
+            None => match self.messages.recv() {
+                Ok(body) => body,
+                Err(_) => return self.disconnect(),
+            },
+        };
+        self.dispatch(&body)
+    }
+
+    fn disconnect(&mut self) -> Next {
+        self.disconnected = true;
+        Next::Disconnect
+    }
+
+    fn dispatch(&mut self, body: &str) -> Next {
+        let request = match Json::parse(body) {
+            Ok(request) => request,
+            Err(_) => return Next::Wait,
+        };
+        let command = request.get("command").and_then(Json::as_str).unwrap_or("");
+        let args = request.get("arguments").cloned().unwrap_or(Json::Null);
+
+        if self.running && !self.stopped && WHILE_STOPPED.contains(&command) {
+            self.pending.push_back(body.to_string());
+            return Next::Wait;
+        }
+
+        let mut next = Next::Wait;
+        let result = match command {
+            "initialize" => Ok(Json::object(vec![(
+                "supportsConfigurationDoneRequest",
+                Json::Bool(true),
+            )])),
+            "launch" => self.configure(&args),
+            "setBreakpoints" => Ok(self.set_breakpoints(&args)),
+            "setExceptionBreakpoints" => Ok(Json::object(vec![])),
+            "configurationDone" if self.program.is_none() => Err("no program launched".to_string()),
+            "configurationDone" => {
+                next = Next::Launch;
+                Ok(Json::object(vec![]))
+            }
+            "threads" => Ok(Json::object(vec![(
+                "threads",
+                Json::Array(vec![Json::object(vec![
+                    ("id", Json::Number(1.0)),
+                    ("name", Json::str("main")),
+                ])]),
+            )])),
+            "stackTrace" => Ok(self.stack_trace()),
+            "scopes" => self.scopes(&args),
+            "variables" => self.variables(&args),
+            "evaluate" => self.evaluate(&args),
+            "continue" | "next" | "stepIn" | "stepOut" if !self.stopped => {
+                Err("the program is not stopped".to_string())
+            }
+            "continue" => {
+                self.session.step = Step::Continue;
+                next = Next::Resume;
+                Ok(Json::object(vec![(
+                    "allThreadsContinued",
+                    Json::Bool(true),
+                )]))
+            }
+            "next" | "stepIn" | "stepOut" => {
+                match command {
+                    "next" => self.session.step_over(),
+                    "stepIn" => self.session.step = Step::Stop("step"),
+                    _ => self.session.step_out(),
+                }
+                next = Next::Resume;
+                Ok(Json::object(vec![]))
+            }
+            "pause" => {
+                self.session.step = Step::Stop("pause");
+                Ok(Json::object(vec![]))
+            }
+            "disconnect" | "terminate" => {
+                next = Next::Disconnect;
+                Ok(Json::object(vec![]))
+            }
+            _ => Err(format!("unsupported request `{}`", command)),
+        };
+
+        self.respond(&request, result);
+        if command == "initialize" {
+            self.event("initialized", Json::object(vec![]));
+        }
+        if next == Next::Disconnect {
+            return self.disconnect();
+        }
+        next
+    }
+
+    fn configure(&mut self, args: &Json) -> Result<Json, String> {
+        let program = args
+            .get("program")
+            .and_then(Json::as_str)
+            .ok_or("`program` is required")?;
+        self.program = Some(program.to_string());
+
+        self.opts.args = args
+            .get("args")
+            .and_then(Json::as_array)
+            .unwrap_or(&[])
+            .iter()
+            .filter_map(|a| a.as_str().map(String::from))
+            .collect();
+        self.opts.compat = args.get("compat").and_then(Json::as_bool) == Some(true);
+        if args.get("stopOnEntry").and_then(Json::as_bool) == Some(true) {
+            self.session.step = Step::Stop("entry");
+        }
+        Ok(Json::object(vec![]))
+    }
+
+    /// Replaces the breakpoints. Scripts are single files, so the source
+    /// they are set in is not checked.
+    fn set_breakpoints(&mut self, args: &Json) -> Json {
+        let lines: Vec<u32> = args
+            .get("breakpoints")
+            .and_then(Json::as_array)
+            .unwrap_or(&[])
+            .iter()
+            .filter_map(|b| b.get("line").and_then(Json::as_f64))
+            .map(|line| line as u32)
+            .collect();
+        self.session.breakpoints = lines.clone();
+
+        let breakpoints = lines
+            .into_iter()
+            .map(|line| {
+                Json::object(vec![
+                    ("verified", Json::Bool(true)),
+                    ("line", Json::Number(line as f64)),
+                ])
+            })
+            .collect();
+        Json::object(vec![("breakpoints", Json::Array(breakpoints))])
+    }
+
+    fn stack_trace(&self) -> Json {
+        let source = Json::object(vec![(
+            "path",
+            Json::String(self.program.clone().unwrap_or_default()),
+        )]);
+
+        // frame ids count from the bottom of the stack, listed from the top
+        let frames: Vec<Json> = self
+            .session
+            .frames
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(i, frame)| {
+                Json::object(vec![
+                    ("id", Json::Number((i + 1) as f64)),
+                    ("name", Json::String(frame.name.clone())),
+                    ("source", source.clone()),
+                    ("line", Json::Number(frame.line as f64)),
+                    ("column", Json::Number(1.0)),
+                ])
+            })
+            .collect();
+
+        Json::object(vec![
+            ("totalFrames", Json::Number(frames.len() as f64)),
+            ("stackFrames", Json::Array(frames)),
+        ])
+    }
+
+    fn frame_env(&self, args: &Json) -> Result<Rc<Env>, String> {
+        let id = args.get("frameId").and_then(Json::as_f64).unwrap_or(0.0) as usize;
+        match id {
+            0 => self.session.frames.last(),
+            id => self.session.frames.get(id - 1),
+        }
+        .map(|frame| frame.env.clone())
+        .ok_or_else(|| "unknown frame".to_string())
+    }
+
+    fn scopes(&mut self, args: &Json) -> Result<Json, String> {
+        let env = self.frame_env(args)?;
+
+        let mut scopes = Vec::new();
+        for (name, env) in debug::scopes(&env) {
+            self.handles.push(env);
+            scopes.push(Json::object(vec![
+                ("name", Json::str(name)),
+                (
+                    "variablesReference",
+                    Json::Number(self.handles.len() as f64),
+                ),
+                ("expensive", Json::Bool(false)),
+            ]));
+        }
+        Ok(Json::object(vec![("scopes", Json::Array(scopes))]))
+    }
+
+    fn variables(&self, args: &Json) -> Result<Json, String> {
+        let handle = args
+            .get("variablesReference")
+            .and_then(Json::as_f64)
+            .unwrap_or(0.0) as usize;
+        let env = handle
+            .checked_sub(1)
+            .and_then(|i| self.handles.get(i))
+            .ok_or("unknown variables reference")?;
+
+        let mut names = env.names();
+        names.sort();
+        let variables = names
+            .iter()
+            .filter_map(|name| {
+                let value = env.get(name).ok()?;
+                Some(Json::object(vec![
+                    ("name", Json::str(name)),
+                    ("value", Json::String(value.to_string())),
+                    ("variablesReference", Json::Number(0.0)),
+                ]))
+            })
+            .collect();
+        Ok(Json::object(vec![("variables", Json::Array(variables))]))
+    }
+
+    fn evaluate(&self, args: &Json) -> Result<Json, String> {
+        let expression = args
+            .get("expression")
+            .and_then(Json::as_str)
+            .ok_or("`expression` is required")?;
+        let value = debug::evaluate(expression, self.frame_env(args)?)?;
+        Ok(Json::object(vec![
+            ("result", Json::String(value.to_string())),
+            ("variablesReference", Json::Number(0.0)),
+        ]))
+    }
+
+    /// Reports the stop and handles requests until the client resumes.
+    fn stop(&mut self, reason: &str, line: u32) -> ResultMSG<()> {
+        self.stopped = true;
+        self.event(
+            "stopped",
+            Json::object(vec![
+                ("reason", Json::str(reason)),
+                ("threadId", Json::Number(1.0)),
+                ("allThreadsStopped", Json::Bool(true)),
+            ]),
+        );
+
+        let next = loop {
+            match self.next() {
+                Next::Wait | Next::Launch => {}
+                next => break next,
+            }
+        };
+        self.stopped = false;
+        self.handles.clear();
+
+        match next {
+            Next::Disconnect => Err(detached(line)),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Hooks for Adapter {
+    fn statement(&mut self, stmt: &Stmt, env: &Rc<Env>) -> ResultMSG<()> {
+        let line = stmt.line().unwrap_or(0);
+
+        // take requests sent while running, such as `pause`
+        while let Ok(body) = self.messages.try_recv() {
+            if self.dispatch(&body) == Next::Disconnect {
+                return Err(detached(line));
+            }
+        }
+
+        match self.session.statement(stmt, env) {
+            Some(reason) => self.stop(reason, line),
+            None => Ok(()),
+        }
+    }
+
+    fn enter(&mut self, name: &str, _body: &Stmt, _args: &[(String, Object)]) {
+        self.session.enter(name);
+    }
+
+    fn exit(&mut self, _name: &str, _result: &ResultMSG<Object>) {
+        self.session.exit();
+    }
+
+    fn print(&mut self, text: &str) -> bool {
+        self.output(&format!("{}\n", text), "stdout");
+        true
+    }
+}
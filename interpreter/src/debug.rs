@@ -0,0 +1,169 @@
+use std::rc::Rc;
+
+use crate::{
+    env::Env,
+    error::Error,
+    interpreter::Interpreter,
+    object::Object,
+    scanner::{Scanner, StmtIterator},
+    stmt::Stmt,
+};
+
+/// Where a paused program should stop next.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Step {
+    /// At a breakpoint.
+    Continue,
+    /// At the next statement, giving this reason.
+    Stop(&'static str),
+    /// At the next statement at this call depth or above.
+    Over(usize),
+    /// At the next statement above this call depth.
+    Out(usize),
+}
+
+/// A function call being run, or the script itself at the bottom.
+pub struct Frame {
+    pub name: String,
+    /// The line of the statement running in this frame.
+    pub line: u32,
+    /// The innermost scope of that statement.
+    pub env: Rc<Env>,
+}
+
+/// The breakpoints, stepping and call stack of a program being debugged.
+/// Debugger front ends feed it the interpreter's hooks and act on the
+/// stops it reports.
+pub struct Session {
+    pub frames: Vec<Frame>,
+    pub breakpoints: Vec<u32>,
+    pub step: Step,
+    /// The depth and line of the last statement, so a breakpoint hits once
+    /// per visit to its line rather than once per statement on it.
+    last: Option<(usize, u32)>,
+    /// The statements run during that visit. One running again means a loop
+    /// on the line came round, which is a new visit.
+    visited: Vec<*const Stmt>,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Session {
+            frames: Vec::new(),
+            breakpoints: Vec::new(),
+            step: Step::Continue,
+            last: None,
+            visited: Vec::new(),
+        }
+    }
+
+    /// Starts the call stack with the script's frame.
+    pub fn start(&mut self, env: Rc<Env>) {
+        self.frames = vec![Frame {
+            name: "<script>".to_string(),
+            line: 0,
+            env,
+        }];
+        self.last = None;
+        self.visited.clear();
+    }
+
+    /// Records the statement about to run and says why the program should
+    /// stop before it, if it should.
+    pub fn statement(&mut self, stmt: &Stmt, env: &Rc<Env>) -> Option<&'static str> {
+        let line = stmt.line().unwrap_or(0);
+        let depth = self.frames.len();
+        if let Some(frame) = self.frames.last_mut() {
+            frame.line = line;
+            frame.env = env.clone();
+        }
+
+        let repeated =
+            self.last == Some((depth, line)) && !self.visited.contains(&(stmt as *const Stmt));
+        if !repeated {
+            self.visited.clear();
+        }
+        self.last = Some((depth, line));
+        self.visited.push(stmt);
+
+        let reason = match self.step {
+            _ if self.breakpoints.contains(&line) && !repeated => "breakpoint",
+            Step::Stop(reason) => reason,
+            Step::Over(d) if depth <= d => "step",
+            Step::Out(d) if depth < d => "step",
+            _ => return None,
+        };
+        self.step = Step::Continue;
+        Some(reason)
+    }
+
+    pub fn enter(&mut self, name: &str) {
+        let (line, env) = match self.frames.last() {
+            Some(frame) => (frame.line, frame.env.clone()),
+            None => (0, Env::new(None)),
+        };
+        self.frames.push(Frame {
+            name: name.to_string(),
+            line,
+            env,
+        });
+    }
+
+    pub fn exit(&mut self) {
+        self.frames.pop();
+    }
+
+    /// Steps to the next statement in the current call.
+    pub fn step_over(&mut self) {
+        self.step = Step::Over(self.frames.len());
+    }
+
+    /// Steps to the statement after the current call returns.
+    pub fn step_out(&mut self) {
+        self.step = Step::Out(self.frames.len());
+    }
+}
+
+/// The error that ends a program when the debugger quits.
+pub fn detached(line: u32) -> Error {
+    Error::Runtime(line, "stopped by the debugger".to_string(), String::new())
+}
+
+/// The scopes visible from `env`, innermost first, each with a name for
+/// display.
+pub fn scopes(env: &Rc<Env>) -> Vec<(&'static str, Rc<Env>)> {
+    let mut scopes = Vec::new();
+    let mut env = Some(env.clone());
+    while let Some(e) = env {
+        env = e.parent();
+        let name = match (scopes.is_empty(), env.is_some()) {
+            (_, false) => "Globals",
+            (true, true) => "Locals",
+            (false, true) => "Enclosing",
+        };
+        scopes.push((name, e));
+    }
+    scopes
+}
+
+/// Evaluates a single expression in `env`, without reporting it to any
+/// hooks.
+pub fn evaluate(code: &str, env: Rc<Env>) -> Result<Object, String> {
+    let mut scanner = Scanner::new(format!("{};", code));
+    scanner.scan_tokens();
+    if let Some(e) = scanner.errors.first() {
+        return Err(e.to_string());
+    }
+
+    let stmts: Vec<Stmt> = scanner
+        .statements()
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    match stmts.as_slice() {
+        [Stmt::Expression(expr)] => Interpreter::new(false)
+            .with_env(env)
+            .evaluate(expr)
+            .map_err(|e| e.to_string()),
+        _ => Err("expected a single expression".to_string()),
+    }
+}
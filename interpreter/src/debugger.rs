@@ -0,0 +1,323 @@
+use std::{
+    cell::RefCell,
+    io::{BufRead, Write},
+    rc::Rc,
+};
+
+use crate::{
+    analyze,
+    debug::{self, detached, Session, Step},
+    env::Env,
+    error::ResultMSG,
+    exit_code,
+    hooks::Hooks,
+    object::Object,
+    read_source,
+    resolver::Resolver,
+    script_interpreter, source_line,
+    stmt::Stmt,
+    Options, EX_IOERR,
+};
+
+const HELP: &str = "\
+break LINE     stop before running LINE (alias b)
+delete LINE    remove the breakpoint at LINE
+run            start the program from the top (alias r)
+next           run to the next line of this function (alias n)
+step           run to the next line, entering calls (alias s)
+finish         run until the current function returns
+continue       run to the next breakpoint (alias c)
+print EXPR     evaluate EXPR where the program stopped (alias p)
+locals         list the variables of the current function
+backtrace      list the calls that led here (alias bt)
+watch NAME     stop whenever the value of NAME changes
+quit           stop debugging (alias q)";
+
+/// What the prompt should do after a command.
+enum Command {
+    Wait,
+    Run,
+    Resume,
+    Quit,
+}
+
+/// A gdb-like command line debugger. It takes commands before the program
+/// starts and whenever it stops.
+struct Debugger {
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+    lines: Vec<String>,
+    session: Session,
+    /// Watched names with the last value seen for each.
+    watches: Vec<(String, Option<String>)>,
+    running: bool,
+    quit: bool,
+}
+
+/// Debugs the script at `path`, reading commands from `input`, and returns
+/// the exit status of the last run.
+pub fn debug(
+    path: &str,
+    opts: &Options,
+    input: Box<dyn BufRead>,
+    mut output: Box<dyn Write>,
+) -> i32 {
+    let code = match read_source(path) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("rlox: cannot read {}: {}", path, e);
+            return EX_IOERR;
+        }
+    };
+
+    let stmts = match analyze(&code, Resolver::new()) {
+        Ok(stmts) => stmts,
+        Err(errors) => {
+            for err in &errors {
+                eprintln!("{}: {}", path, err);
+            }
+            return exit_code(&errors[0]);
+        }
+    };
+
+    let _ = writeln!(output, "debugging {}, type `help` for the commands", path);
+    let debugger = Rc::new(RefCell::new(Debugger {
+        input,
+        output,
+        lines: code.lines().map(String::from).collect(),
+        session: Session::new(),
+        watches: Vec::new(),
+        running: false,
+        quit: false,
+    }));
+
+    let mut status = 0;
+    loop {
+        let command = debugger.borrow_mut().prompt();
+        match command {
+            Command::Run => {
+                status = run(&debugger, &stmts, opts);
+                let mut debugger = debugger.borrow_mut();
+                if debugger.quit {
+                    break;
+                }
+                debugger.say(&format!("program exited with status {}", status));
+            }
+            Command::Quit => break,
+            _ => {}
+        }
+    }
+    status
+}
+
+fn run(debugger: &Rc<RefCell<Debugger>>, stmts: &[Stmt], opts: &Options) -> i32 {
+    let interpreter = script_interpreter(opts);
+    {
+        let mut debugger = debugger.borrow_mut();
+        debugger.session.start(interpreter.env.clone());
+        debugger.running = true;
+    }
+    let mut interpreter = interpreter.hooks(debugger.clone());
+
+    let mut status = 0;
+    for stmt in stmts {
+        if let Err(e) = interpreter.interpret(stmt) {
+            let mut debugger = debugger.borrow_mut();
+            if !debugger.quit {
+                debugger.say(&e.to_string());
+            }
+            status = exit_code(&e);
+            break;
+        }
+    }
+
+    debugger.borrow_mut().running = false;
+    status
+}
+
+impl Debugger {
+    fn say(&mut self, text: &str) {
+        let _ = writeln!(self.output, "{}", text);
+    }
+
+    /// Reads and runs one command. The end of input quits.
+    fn prompt(&mut self) -> Command {
+        let _ = write!(self.output, "(rlox) ");
+        let _ = self.output.flush();
+
+        let mut line = String::new();
+        match self.input.read_line(&mut line) {
+            Ok(0) | Err(_) => {
+                self.quit = true;
+                Command::Quit
+            }
+            Ok(_) => self.command(line.trim()),
+        }
+    }
+
+    fn command(&mut self, line: &str) -> Command {
+        let (name, arg) = match line.split_once(char::is_whitespace) {
+            Some((name, arg)) => (name, arg.trim()),
+            None => (line, ""),
+        };
+
+        match name {
+            "" => {}
+            "help" | "h" => self.say(HELP),
+            "break" | "b" | "delete" => match arg.parse::<u32>() {
+                Ok(line) if name == "delete" => {
+                    self.session.breakpoints.retain(|l| *l != line);
+                }
+                Ok(line) => {
+                    self.session.breakpoints.push(line);
+                    self.say(&format!("breakpoint at line {}", line));
+                }
+                Err(_) => self.say(&format!("usage: {} LINE", name)),
+            },
+            "watch" if arg.is_empty() => self.say("usage: watch NAME"),
+            "watch" => {
+                let value = self.value(arg);
+                self.watches.push((arg.to_string(), value));
+                self.say(&format!("watching {}", arg));
+            }
+            "quit" | "q" => {
+                self.quit = true;
+                return Command::Quit;
+            }
+            "run" | "r" if self.running => self.say("the program is already running"),
+            "run" | "r" => return Command::Run,
+            _ if !self.running => self.say("the program is not running, type `run` to start it"),
+            "continue" | "c" => {
+                self.session.step = Step::Continue;
+                return Command::Resume;
+            }
+            "next" | "n" => {
+                self.session.step_over();
+                return Command::Resume;
+            }
+            "step" | "s" => {
+                self.session.step = Step::Stop("step");
+                return Command::Resume;
+            }
+            "finish" => {
+                self.session.step_out();
+                return Command::Resume;
+            }
+            "print" | "p" => {
+                let env = self.env();
+                match debug::evaluate(arg, env) {
+                    Ok(value) => self.say(&value.to_string()),
+                    Err(e) => self.say(&e),
+                }
+            }
+            "locals" => self.locals(),
+            "backtrace" | "bt" => {
+                let frames: Vec<String> = self
+                    .session
+                    .frames
+                    .iter()
+                    .rev()
+                    .enumerate()
+                    .map(|(i, frame)| format!("#{} {} at line {}", i, frame.name, frame.line))
+                    .collect();
+                self.say(&frames.join("\n"));
+            }
+            _ => self.say(&format!("unknown command `{}`, see help", name)),
+        }
+        Command::Wait
+    }
+
+    /// The innermost scope of the current frame.
+    fn env(&self) -> Rc<Env> {
+        match self.session.frames.last() {
+            Some(frame) => frame.env.clone(),
+            None => Env::new(None),
+        }
+    }
+
+    fn value(&self, name: &str) -> Option<String> {
+        self.env().get(name).ok().map(|v| v.to_string())
+    }
+
+    /// Lists the variables of every scope of the current frame up to the
+    /// globals, innermost first.
+    fn locals(&mut self) {
+        let mut out: Vec<String> = Vec::new();
+        for (_, env) in debug::scopes(&self.env()) {
+            if env.parent().is_none() {
+                break;
+            }
+            let mut names = env.names();
+            names.sort();
+            for name in names {
+                if let Ok(value) = env.get(&name) {
+                    out.push(format!("{} = {}", name, value));
+                }
+            }
+        }
+
+        match out.is_empty() {
+            true => self.say("no locals"),
+            false => self.say(&out.join("\n")),
+        }
+    }
+
+    /// Describes the first watched name whose value changed, remembering
+    /// the new values. Names out of scope keep their last value.
+    fn watch(&mut self) -> Option<String> {
+        let mut changed = None;
+        for i in 0..self.watches.len() {
+            let value = match self.value(&self.watches[i].0) {
+                Some(value) => value,
+                None => continue,
+            };
+
+            let (name, last) = &mut self.watches[i];
+            if last.as_ref() != Some(&value) {
+                let old = last
+                    .replace(value.clone())
+                    .unwrap_or("undefined".to_string());
+                changed.get_or_insert(format!("{} changed from {} to {}", name, old, value));
+            }
+        }
+        changed
+    }
+}
+
+impl Hooks for Debugger {
+    fn statement(&mut self, stmt: &Stmt, env: &Rc<Env>) -> ResultMSG<()> {
+        let line = stmt.line().unwrap_or(0);
+        let reason = self.session.statement(stmt, env);
+        let changed = self.watch();
+
+        match (changed, reason) {
+            (Some(changed), _) => self.say(&changed),
+            (None, Some("breakpoint")) => self.say(&format!("breakpoint at line {}", line)),
+            (None, Some(_)) => {}
+            (None, None) => return Ok(()),
+        }
+        let text = format!("{}\t{}", line, source_line(&self.lines, line));
+        self.say(&text);
+
+        loop {
+            match self.prompt() {
+                Command::Resume => return Ok(()),
+                Command::Quit => return Err(detached(line)),
+                _ => {}
+            }
+        }
+    }
+
+    fn enter(&mut self, name: &str, _body: &Stmt, _args: &[(String, Object)]) {
+        self.session.enter(name);
+    }
+
+    fn exit(&mut self, _name: &str, _result: &ResultMSG<Object>) {
+        self.session.exit();
+    }
+
+    fn print(&mut self, text: &str) -> bool {
+        self.say(text);
+        true
+    }
+}
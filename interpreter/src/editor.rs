@@ -0,0 +1,535 @@
+use std::{
+    env, fs,
+    io::{self, BufRead, IsTerminal, Read, Write},
+    path::PathBuf,
+};
+
+use raw::RawMode;
+
+const HISTORY_FILE: &str = ".rlox_history";
+const HISTORY_LIMIT: usize = 1000;
+
+/// A key press decoded from the terminal's byte stream.
+#[derive(Debug, PartialEq)]
+enum Key {
+    Char(char),
+    Enter,
+    Backspace,
+    Delete,
+    Tab,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    KillToEnd,
+    KillToStart,
+    KillWord,
+    Search,
+    Cancel,
+    Interrupt,
+    Eof,
+    Closed,
+    Other,
+}
+
+/// Raw terminal input through a small binding to `<termios.h>`.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios"
+))]
+mod raw {
+    use std::{io, os::raw::c_int};
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    mod consts {
+        pub type Flag = u32;
+        pub const NCCS: usize = 32;
+        pub const ISIG: Flag = 0o1;
+        pub const ICANON: Flag = 0o2;
+        pub const ECHO: Flag = 0o10;
+        pub const IEXTEN: Flag = 0o100000;
+        pub const ICRNL: Flag = 0o400;
+        pub const IXON: Flag = 0o2000;
+        pub const VTIME: usize = 5;
+        pub const VMIN: usize = 6;
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    mod consts {
+        pub type Flag = std::os::raw::c_ulong;
+        pub const NCCS: usize = 20;
+        pub const ISIG: Flag = 0x80;
+        pub const ICANON: Flag = 0x100;
+        pub const ECHO: Flag = 0x8;
+        pub const IEXTEN: Flag = 0x400;
+        pub const ICRNL: Flag = 0x100;
+        pub const IXON: Flag = 0x200;
+        pub const VTIME: usize = 17;
+        pub const VMIN: usize = 16;
+    }
+
+    use consts::*;
+
+    const STDIN: c_int = 0;
+    const TCSANOW: c_int = 0;
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct Termios {
+        iflag: Flag,
+        oflag: Flag,
+        cflag: Flag,
+        lflag: Flag,
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        line: u8,
+        cc: [u8; NCCS],
+        ispeed: Flag,
+        ospeed: Flag,
+    }
+
+    extern "C" {
+        fn tcgetattr(fd: c_int, termios: *mut Termios) -> c_int;
+        fn tcsetattr(fd: c_int, action: c_int, termios: *const Termios) -> c_int;
+    }
+
+    /// Puts the terminal into raw mode and restores the previous settings
+    /// when dropped. Only input is made raw: output processing stays on, so
+    /// whatever the program prints between prompts still starts its lines
+    /// at the left margin.
+    pub struct RawMode {
+        saved: Termios,
+    }
+
+    impl RawMode {
+        pub fn enable() -> io::Result<RawMode> {
+            // SAFETY: `Termios` is plain data matching the C layout, and
+            // `tcgetattr` fills it in before it is read.
+            let mut saved: Termios = unsafe { std::mem::zeroed() };
+            if unsafe { tcgetattr(STDIN, &mut saved) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let mut raw = saved;
+            raw.iflag &= !(ICRNL | IXON);
+            raw.lflag &= !(ECHO | ICANON | IEXTEN | ISIG);
+            raw.cc[VMIN] = 1;
+            raw.cc[VTIME] = 0;
+            if unsafe { tcsetattr(STDIN, TCSANOW, &raw) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            Ok(RawMode { saved })
+        }
+    }
+
+    impl Drop for RawMode {
+        fn drop(&mut self) {
+            unsafe { tcsetattr(STDIN, TCSANOW, &self.saved) };
+        }
+    }
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios"
+)))]
+mod raw {
+    use std::io;
+
+    pub struct RawMode;
+
+    impl RawMode {
+        pub fn enable() -> io::Result<RawMode> {
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "raw mode is not supported on this platform",
+            ))
+        }
+    }
+}
+
+/// The line being edited and the cursor position within it, in chars.
+struct Line {
+    buf: Vec<char>,
+    pos: usize,
+}
+
+impl Line {
+    fn set(&mut self, text: &str) {
+        self.buf = text.chars().collect();
+        self.pos = self.buf.len();
+    }
+
+    fn text(&self) -> String {
+        self.buf.iter().collect()
+    }
+
+    /// The identifier-like word that ends at the cursor.
+    fn word(&self) -> String {
+        let start = self.buf[..self.pos]
+            .iter()
+            .rposition(|c| !(c.is_alphanumeric() || *c == '_'))
+            .map_or(0, |i| i + 1);
+        self.buf[start..self.pos].iter().collect()
+    }
+
+    fn insert(&mut self, text: &str) {
+        for c in text.chars() {
+            self.buf.insert(self.pos, c);
+            self.pos += 1;
+        }
+    }
+}
+
+/// A minimal line editor for the REPL with history, reverse search and tab
+/// completion. The terminal is switched to raw mode on the first read and
+/// stays that way until the editor is dropped. Falls back to plain buffered
+/// reads when stdin is not a terminal or raw mode cannot be enabled.
+pub struct Editor {
+    history: Vec<String>,
+    path: Option<PathBuf>,
+    raw: Option<RawMode>,
+    started: bool,
+}
+
+impl Editor {
+    /// Creates an editor whose history is loaded from and saved to `path`.
+    pub fn new(path: Option<PathBuf>) -> Self {
+        let history = path
+            .as_ref()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .map(|h| h.lines().map(String::from).collect())
+            .unwrap_or_default();
+
+        Editor {
+            history,
+            path,
+            raw: None,
+            started: false,
+        }
+    }
+
+    /// `~/.rlox_history`, when the home directory is known.
+    pub fn history_path() -> Option<PathBuf> {
+        env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE))
+    }
+
+    /// Reads one line without its newline, or `None` at end of input.
+    /// Ctrl-C discards the line and reports `ErrorKind::Interrupted`.
+    pub fn read_line(
+        &mut self,
+        prompt: &str,
+        complete: &dyn Fn(&str) -> Vec<String>,
+    ) -> io::Result<Option<String>> {
+        let stdin = io::stdin();
+        let mut stdout = io::stdout();
+
+        if !self.started {
+            self.started = true;
+            if stdin.is_terminal() {
+                match RawMode::enable() {
+                    Ok(raw) => self.raw = Some(raw),
+                    Err(e) => eprintln!("rlox: line editing disabled: {}", e),
+                }
+            }
+        }
+
+        if self.raw.is_none() {
+            write!(stdout, "{}", prompt)?;
+            stdout.flush()?;
+            let mut line = String::new();
+            if stdin.lock().read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            return Ok(Some(line.trim_end_matches(['\n', '\r']).to_string()));
+        }
+
+        let res = self.edit(prompt, &mut stdin.lock(), &mut stdout, complete);
+
+        if let Ok(Some(ref line)) = res {
+            self.add_history(line);
+        }
+        res
+    }
+
+    pub(crate) fn add_history(&mut self, line: &str) {
+        if line.trim().is_empty() || self.history.last().map(String::as_str) == Some(line) {
+            return;
+        }
+
+        self.history.push(line.to_string());
+        if self.history.len() > HISTORY_LIMIT {
+            self.history.remove(0);
+        }
+
+        if let Some(ref path) = self.path {
+            let _ = fs::write(path, self.history.join("\n") + "\n");
+        }
+    }
+
+    /// Runs the editing loop over raw key input until a line is entered.
+    pub(crate) fn edit(
+        &mut self,
+        prompt: &str,
+        input: &mut dyn Read,
+        out: &mut dyn Write,
+        complete: &dyn Fn(&str) -> Vec<String>,
+    ) -> io::Result<Option<String>> {
+        let mut line = Line {
+            buf: Vec::new(),
+            pos: 0,
+        };
+        // index into history while browsing it with the arrow keys
+        let mut browsing = self.history.len();
+        let mut pending: Option<Key> = None;
+
+        render(out, prompt, &line)?;
+        loop {
+            let key = match pending.take() {
+                Some(key) => key,
+                None => read_key(input)?,
+            };
+
+            match key {
+                Key::Char(c) => line.insert(&c.to_string()),
+                Key::Enter => {
+                    write!(out, "\r\n")?;
+                    out.flush()?;
+                    return Ok(Some(line.text()));
+                }
+                Key::Eof | Key::Closed if line.buf.is_empty() => {
+                    write!(out, "\r\n")?;
+                    out.flush()?;
+                    return Ok(None);
+                }
+                Key::Closed => return Ok(Some(line.text())),
+                Key::Eof | Key::Delete => {
+                    if line.pos < line.buf.len() {
+                        line.buf.remove(line.pos);
+                    }
+                }
+                Key::Interrupt => {
+                    write!(out, "^C\r\n")?;
+                    out.flush()?;
+                    return Err(io::Error::from(io::ErrorKind::Interrupted));
+                }
+                Key::Backspace => {
+                    if line.pos > 0 {
+                        line.pos -= 1;
+                        line.buf.remove(line.pos);
+                    }
+                }
+                Key::Left => line.pos = line.pos.saturating_sub(1),
+                Key::Right => line.pos = (line.pos + 1).min(line.buf.len()),
+                Key::Home => line.pos = 0,
+                Key::End => line.pos = line.buf.len(),
+                Key::KillToEnd => line.buf.truncate(line.pos),
+                Key::KillToStart => {
+                    line.buf.drain(..line.pos);
+                    line.pos = 0;
+                }
+                Key::KillWord => {
+                    let word = line.buf[..line.pos]
+                        .iter()
+                        .rev()
+                        .skip_while(|c| c.is_whitespace())
+                        .skip_while(|c| !c.is_whitespace())
+                        .count();
+                    line.buf.drain(word..line.pos);
+                    line.pos = word;
+                }
+                Key::Up if browsing > 0 => {
+                    browsing -= 1;
+                    line.set(&self.history[browsing]);
+                }
+                Key::Down if browsing < self.history.len() => {
+                    browsing += 1;
+                    match self.history.get(browsing) {
+                        Some(entry) => line.set(entry),
+                        None => line.set(""),
+                    }
+                }
+                Key::Tab => {
+                    let word = line.word();
+                    let mut matches = complete(&word);
+                    matches.sort();
+                    matches.dedup();
+
+                    let common = common_prefix(&matches);
+                    if common.chars().count() > word.chars().count() {
+                        line.insert(&common[word.len()..]);
+                    } else if matches.len() > 1 {
+                        write!(out, "\r\n{}\r\n", matches.join("  "))?;
+                    }
+                }
+                Key::Search => pending = self.search(input, out, &mut line)?,
+                _ => {}
+            }
+
+            render(out, prompt, &line)?;
+        }
+    }
+
+    /// Ctrl-R: searches backwards through history for lines containing the
+    /// typed text. Any key other than text, backspace or another Ctrl-R
+    /// leaves the match in `line` and is handed back to the editing loop.
+    fn search(
+        &self,
+        input: &mut dyn Read,
+        out: &mut dyn Write,
+        line: &mut Line,
+    ) -> io::Result<Option<Key>> {
+        let mut query = String::new();
+        let mut found: Option<usize> = None;
+
+        loop {
+            let shown = found.map_or("", |i| self.history[i].as_str());
+            write!(out, "\r(reverse-i-search)`{}': {}\x1b[K", query, shown)?;
+            out.flush()?;
+
+            // search history entries older than `before`
+            let before = match read_key(input)? {
+                Key::Char(c) => {
+                    query.push(c);
+                    found.map_or(self.history.len(), |i| i + 1)
+                }
+                Key::Backspace => {
+                    query.pop();
+                    self.history.len()
+                }
+                Key::Search => found.unwrap_or(self.history.len()),
+                Key::Cancel | Key::Interrupt => return Ok(None),
+                key => {
+                    if let Some(i) = found {
+                        line.set(&self.history[i]);
+                    }
+                    return Ok(Some(key));
+                }
+            };
+
+            let older = self.history[..before]
+                .iter()
+                .rposition(|h| h.contains(&query));
+            found = older.or(found);
+        }
+    }
+}
+
+fn render(out: &mut dyn Write, prompt: &str, line: &Line) -> io::Result<()> {
+    write!(out, "\r{}{}\x1b[K", prompt, line.text())?;
+    let back = line.buf.len() - line.pos;
+    if back > 0 {
+        write!(out, "\x1b[{}D", back)?;
+    }
+    out.flush()
+}
+
+fn common_prefix(words: &[String]) -> String {
+    let first = match words.first() {
+        Some(first) => first,
+        None => return String::new(),
+    };
+
+    let mut prefix = first.clone();
+    for word in &words[1..] {
+        while !word.starts_with(&prefix) {
+            prefix.pop();
+        }
+    }
+    prefix
+}
+
+fn read_byte(input: &mut dyn Read) -> io::Result<Option<u8>> {
+    let mut byte = [0u8];
+    match input.read(&mut byte)? {
+        0 => Ok(None),
+        _ => Ok(Some(byte[0])),
+    }
+}
+
+fn read_key(input: &mut dyn Read) -> io::Result<Key> {
+    let byte = match read_byte(input)? {
+        Some(b) => b,
+        None => return Ok(Key::Closed),
+    };
+
+    let key = match byte {
+        1 => Key::Home,
+        2 => Key::Left,
+        3 => Key::Interrupt,
+        4 => Key::Eof,
+        5 => Key::End,
+        6 => Key::Right,
+        7 => Key::Cancel,
+        8 | 127 => Key::Backspace,
+        9 => Key::Tab,
+        11 => Key::KillToEnd,
+        13 | 10 => Key::Enter,
+        14 => Key::Down,
+        16 => Key::Up,
+        18 => Key::Search,
+        21 => Key::KillToStart,
+        23 => Key::KillWord,
+        27 => read_escape(input)?,
+        b if b < 32 => Key::Other,
+        b => {
+            let len = match b {
+                0xf0..=0xff => 4,
+                0xe0..=0xef => 3,
+                0xc0..=0xdf => 2,
+                _ => 1,
+            };
+            let mut bytes = vec![b];
+            for _ in 1..len {
+                bytes.extend(read_byte(input)?);
+            }
+            match String::from_utf8_lossy(&bytes).chars().next() {
+                Some(c) => Key::Char(c),
+                None => Key::Other,
+            }
+        }
+    };
+
+    Ok(key)
+}
+
+/// Decodes the rest of an `ESC [ ...` or `ESC O ...` sequence.
+fn read_escape(input: &mut dyn Read) -> io::Result<Key> {
+    match read_byte(input)? {
+        Some(b'[') | Some(b'O') => {}
+        _ => return Ok(Key::Cancel),
+    }
+
+    let key = match read_byte(input)? {
+        Some(b'A') => Key::Up,
+        Some(b'B') => Key::Down,
+        Some(b'C') => Key::Right,
+        Some(b'D') => Key::Left,
+        Some(b'H') => Key::Home,
+        Some(b'F') => Key::End,
+        Some(d) if d.is_ascii_digit() => {
+            let mut code = vec![d];
+            loop {
+                match read_byte(input)? {
+                    Some(b'~') | None => break,
+                    Some(b) => code.push(b),
+                }
+            }
+            match code.as_slice() {
+                b"1" | b"7" => Key::Home,
+                b"3" => Key::Delete,
+                b"4" | b"8" => Key::End,
+                _ => Key::Other,
+            }
+        }
+        _ => Key::Other,
+    };
+
+    Ok(key)
+}
@@ -1,13 +1,27 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
 
 use crate::{
     error::{Error, ResultMSG},
     object::Object,
 };
 
+/// The bindings of a scope at some point, taken by `Env::snapshot`.
+pub struct Snapshot {
+    vals: HashMap<String, Object>,
+    consts: HashSet<String>,
+    builtins: HashSet<String>,
+}
+
 pub struct Env {
     parent: Option<Rc<Env>>,
     vals: RefCell<HashMap<String, Object>>,
+    consts: RefCell<HashSet<String>>,
+    /// Natives that a user declaration of the same name may replace.
+    builtins: RefCell<HashSet<String>>,
 }
 
 impl Env {
@@ -15,12 +29,16 @@ impl Env {
         Rc::new(Env {
             parent,
             vals: RefCell::new(HashMap::new()),
+            consts: RefCell::new(HashSet::new()),
+            builtins: RefCell::new(HashSet::new()),
         })
     }
 
     pub fn with_parent(parent: Rc<Env>) -> Rc<Env> {
         Rc::new(Env {
             vals: RefCell::new(HashMap::new()),
+            consts: RefCell::new(HashSet::new()),
+            builtins: RefCell::new(HashSet::new()),
             parent: Some(parent),
         })
     }
@@ -35,7 +53,7 @@ impl Env {
     pub fn define(&self, name: &str, val: Object) -> ResultMSG<()> {
         let mut vals = self.vals.borrow_mut();
 
-        if vals.contains_key(name) {
+        if vals.contains_key(name) && !self.builtins.borrow_mut().remove(name) {
             return Err(Error::Runtime(
                 0,
                 format!("variable `{}` already defined", name),
@@ -46,6 +64,20 @@ impl Env {
         Ok(())
     }
 
+    pub fn define_const(&self, name: &str, val: Object) -> ResultMSG<()> {
+        self.define(name, val)?;
+        self.consts.borrow_mut().insert(name.to_owned());
+        Ok(())
+    }
+
+    /// Defines a native that later declarations may replace, so scripts can
+    /// still use its name for their own variables and functions.
+    pub fn define_builtin(&self, name: &str, val: Object) -> ResultMSG<()> {
+        self.define(name, val)?;
+        self.builtins.borrow_mut().insert(name.to_owned());
+        Ok(())
+    }
+
     pub fn assign(&self, name: &str, val: Object) -> ResultMSG<Object> {
         let mut vals = self.vals.borrow_mut();
 
@@ -61,10 +93,45 @@ impl Env {
             ));
         }
 
+        if self.consts.borrow().contains(name) {
+            return Err(Error::Runtime(
+                0,
+                format!("cannot assign to constant `{}`", name),
+                "".to_string(),
+            ));
+        }
+
         let _ = vals.insert(name.to_owned(), val.clone());
         Ok(val)
     }
 
+    /// The enclosing scope, `None` for the globals.
+    pub fn parent(&self) -> Option<Rc<Env>> {
+        self.parent.clone()
+    }
+
+    /// The names bound directly in this scope, not in its parents.
+    pub fn names(&self) -> Vec<String> {
+        self.vals.borrow().keys().cloned().collect()
+    }
+
+    /// A copy of the bindings of this scope, not of its parents.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            vals: self.vals.borrow().clone(),
+            consts: self.consts.borrow().clone(),
+            builtins: self.builtins.borrow().clone(),
+        }
+    }
+
+    /// Puts back the bindings of `snapshot`, undoing the declarations and
+    /// assignments made in this scope since it was taken.
+    pub fn restore(&self, snapshot: Snapshot) {
+        *self.vals.borrow_mut() = snapshot.vals;
+        *self.consts.borrow_mut() = snapshot.consts;
+        *self.builtins.borrow_mut() = snapshot.builtins;
+    }
+
     pub fn get(&self, name: &str) -> ResultMSG<Object> {
         let vals = self.vals.borrow();
 
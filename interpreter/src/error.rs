@@ -7,7 +7,9 @@ pub type ResultMSG<T> = Result<T, Error>;
 
 #[derive(Debug)]
 pub enum Error {
+    Lexer(u32, String, String),
     Parser(u32, String, String),
+    Resolve(u32, String, String),
     Runtime(u32, String, String),
     Break(u32),
     Return(u64, Object),
@@ -16,11 +18,19 @@ pub enum Error {
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
+            Error::Lexer(line, msg, near) => {
+                write!(f, "Lex Error at [line: {}] {} : near {} ", line, msg, &near)
+            }
             Error::Parser(line, msg, near) => write!(
                 f,
                 "Parse Error at [line: {}] {} : near {} ",
                 line, msg, &near
             ),
+            Error::Resolve(line, msg, near) => write!(
+                f,
+                "Resolve Error at [line: {}] {} : near {} ",
+                line, msg, &near
+            ),
             Error::Runtime(line, msg, near) => write!(
                 f,
                 "Runtime Error at [line: {}] {} : near {} ",
@@ -43,7 +53,9 @@ impl fmt::Display for Error {
 impl error::Error for Error {
     fn description(&self) -> &str {
         match *self {
+            Error::Lexer(_, _, _) => "lex error",
             Error::Parser(_, _, _) => "parse error",
+            Error::Resolve(_, _, _) => "resolve error",
             Error::Runtime(_, _, _) => "runtime error",
             Error::Break(_) => "break error",
             Error::Return(_, _) => "return error",
@@ -61,4 +73,13 @@ impl Error {
     pub fn boxed(self) -> Box<Error> {
         Box::new(self)
     }
+
+    /// Fills in the line of runtime errors raised where it is not known,
+    /// such as inside `Env`.
+    pub fn at_line(self, line: u32) -> Error {
+        match self {
+            Error::Runtime(0, msg, near) => Error::Runtime(line, msg, near),
+            e => e,
+        }
+    }
 }
@@ -1,16 +1,38 @@
 use crate::token::Token;
 
 pub enum Expr {
-    Identifier(String),
+    Identifier(Token),
     Literal(Token),
     Grouping(Box<Expr>),
     Unary(Token, Box<Expr>),
     Binary(Box<Expr>, Token, Box<Expr>),
-    Assignment(String, Box<Expr>),
-    Call(Box<Expr>, Token, Vec<Expr>),
+    Assignment(Token, Box<Expr>),
+    Compound(Token, Token, Box<Expr>),
+    Update(Token, Token, bool),
+    Call(Box<Expr>, Token, Vec<Expr>, Vec<(Token, Expr)>),
+    Index(Box<Expr>, Token, Box<Expr>),
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+    Get(Box<Expr>, Token, bool),
+    Interpolation(Vec<Expr>),
 }
 
 impl Expr {
+    /// The line where the expression starts.
+    pub fn line(&self) -> u32 {
+        match self {
+            Expr::Identifier(t) | Expr::Literal(t) => t.line,
+            Expr::Unary(op, _) => op.line,
+            Expr::Assignment(t, _) | Expr::Compound(t, _, _) | Expr::Update(t, _, _) => t.line,
+            Expr::Grouping(e)
+            | Expr::Binary(e, _, _)
+            | Expr::Call(e, _, _, _)
+            | Expr::Index(e, _, _)
+            | Expr::Conditional(e, _, _)
+            | Expr::Get(e, _, _) => e.line(),
+            Expr::Interpolation(parts) => parts.first().map_or(0, |p| p.line()),
+        }
+    }
+
     pub fn accept<T>(&self, v: &mut dyn Visitor<T>) -> T {
         v.visit_expr(self)
     }
@@ -39,15 +61,51 @@ pub trait Visitor<T> {
         self.visit_expr(expr)
     }
 
-    fn visit_identifier(&mut self, expr: &Expr, n: &String) -> T {
+    fn visit_identifier(&mut self, expr: &Expr, n: &Token) -> T {
+        self.visit_expr(expr)
+    }
+
+    fn visit_assignment(&mut self, expr: &Expr, n: &Token, rhs: &Box<Expr>) -> T {
+        self.visit_expr(expr)
+    }
+
+    fn visit_compound(&mut self, expr: &Expr, _n: &Token, _op: &Token, _rhs: &Expr) -> T {
+        self.visit_expr(expr)
+    }
+
+    fn visit_update(&mut self, expr: &Expr, _n: &Token, _op: &Token, _prefix: bool) -> T {
+        self.visit_expr(expr)
+    }
+
+    fn visit_call(
+        &mut self,
+        expr: &Expr,
+        paren: &Token,
+        params: &[Expr],
+        _named: &[(Token, Expr)],
+    ) -> T {
+        self.visit_expr(expr)
+    }
+
+    fn visit_index(&mut self, expr: &Expr, _object: &Expr, _bracket: &Token, _index: &Expr) -> T {
+        self.visit_expr(expr)
+    }
+
+    fn visit_conditional(
+        &mut self,
+        expr: &Expr,
+        _cond: &Expr,
+        _then_expr: &Expr,
+        _else_expr: &Expr,
+    ) -> T {
         self.visit_expr(expr)
     }
 
-    fn visit_assignment(&mut self, expr: &Expr, n: &String, rhs: &Box<Expr>) -> T {
+    fn visit_get(&mut self, expr: &Expr, _object: &Expr, _name: &Token, _safe: bool) -> T {
         self.visit_expr(expr)
     }
 
-    fn visit_call(&mut self, expr: &Expr, paren: &Token, params: &[Expr]) -> T {
+    fn visit_interpolation(&mut self, expr: &Expr, _parts: &[Expr]) -> T {
         self.visit_expr(expr)
     }
 }
@@ -0,0 +1,388 @@
+use crate::{
+    error::{Error, ResultMSG},
+    scanner::{Scanner, StmtIterator},
+    token::{Token, TokenType, TokenType::*},
+};
+
+const INDENT: &str = "    ";
+const WIDTH: usize = 80;
+
+/// Re-prints a program in the canonical layout: one statement per line,
+/// four-space indentation, single spaces around binary operators, and calls
+/// that do not fit in the line width split one argument per line. Comments
+/// and single blank lines between statements are kept.
+pub fn format(code: &str) -> ResultMSG<String> {
+    // refuse to touch code that does not parse rather than guess at it
+    let mut scanner = Scanner::new(code.to_string());
+    scanner.scan_tokens();
+    if let Some(e) = scanner.errors.drain(..).next() {
+        return Err(e);
+    }
+    if let Some(e) = scanner.statements().find_map(|res| res.err()) {
+        return Err(e);
+    }
+
+    let mut tokens = Scanner::new(code.to_string()).lossless(true).scan_tokens();
+    tokens.pop();
+
+    let mut f = Formatter {
+        tokens: &tokens,
+        lines: Vec::new(),
+        pending: Vec::new(),
+        indent: 0,
+        parens: 0,
+        continued: false,
+        last_line: 0,
+    };
+    f.format();
+
+    let mut out = f.lines.join("\n");
+    out.push('\n');
+
+    // the layout may only ever change whitespace
+    if significant(code) != significant(&out) {
+        return Err(Error::Parser(
+            0,
+            "formatting would change the program".to_string(),
+            String::new(),
+        ));
+    }
+    Ok(out)
+}
+
+/// The lossless tokens of `code`, ignoring where they sit.
+fn significant(code: &str) -> Vec<(TokenType, String)> {
+    Scanner::new(code.to_string())
+        .lossless(true)
+        .scan_tokens()
+        .into_iter()
+        .map(|t| (t.token_type, t.lexeme.trim_end().to_string()))
+        .collect()
+}
+
+struct Formatter<'a> {
+    tokens: &'a [Token],
+    lines: Vec<String>,
+    /// Tokens of the statement line being collected.
+    pending: Vec<&'a Token>,
+    indent: usize,
+    /// Depth of parentheses, inside which `;` does not end a line.
+    parens: usize,
+    /// Whether `pending` continues a statement broken by a comment.
+    continued: bool,
+    /// The source line where the last token written ends.
+    last_line: u32,
+}
+
+impl<'a> Formatter<'a> {
+    fn format(&mut self) {
+        let tokens = self.tokens;
+
+        for (i, token) in tokens.iter().enumerate() {
+            let next = tokens.get(i + 1);
+
+            if self.pending.is_empty() && !self.lines.is_empty() && token.line > self.last_line + 1
+            {
+                let after_open = self.lines.last().map_or(true, |l| l.ends_with('{'));
+                let before_close = token.token_type == RIGHT_BRACE;
+                if !after_open && !before_close && !self.continued {
+                    self.lines.push(String::new());
+                }
+            }
+
+            match token.token_type {
+                COMMENT | DOC_COMMENT => self.comment(token, next),
+                LEFT_BRACE if next.map(|n| n.token_type) == Some(RIGHT_BRACE) => {
+                    self.pending.push(token);
+                }
+                LEFT_BRACE => {
+                    self.pending.push(token);
+                    self.flush();
+                    self.indent += 1;
+                }
+                RIGHT_BRACE if self.pending.last().map(|t| t.token_type) == Some(LEFT_BRACE) => {
+                    self.pending.push(token);
+                    self.end_statement(next);
+                }
+                RIGHT_BRACE => {
+                    self.flush();
+                    self.indent = self.indent.saturating_sub(1);
+                    self.pending.push(token);
+                    self.end_statement(next);
+                }
+                SEMICOLON if self.parens == 0 => {
+                    self.pending.push(token);
+                    self.end_statement(next);
+                }
+                LEFT_PAREN => {
+                    self.parens += 1;
+                    self.pending.push(token);
+                }
+                RIGHT_PAREN => {
+                    self.parens = self.parens.saturating_sub(1);
+                    self.pending.push(token);
+                }
+                _ => self.pending.push(token),
+            }
+
+            self.last_line = end_line(token);
+        }
+        self.flush();
+    }
+
+    /// Ends the line after a statement unless an `else` follows, which
+    /// stays on the line of its `if`.
+    fn end_statement(&mut self, next: Option<&Token>) {
+        if next.map(|n| n.token_type) != Some(ELSE) {
+            self.flush();
+            self.continued = false;
+        }
+    }
+
+    fn comment(&mut self, token: &'a Token, next: Option<&Token>) {
+        let inline = token.line == self.last_line
+            && next.map_or(false, |n| n.line == end_line(token))
+            && !token.lexeme.starts_with("//");
+
+        if inline && !self.pending.is_empty() {
+            self.pending.push(token);
+            return;
+        }
+
+        // a comment after code on the same source line stays there
+        if token.line == self.last_line && !self.lines.is_empty() && self.pending.is_empty() {
+            let last = self.lines.last_mut().unwrap();
+            last.push(' ');
+            last.push_str(&token.lexeme);
+            return;
+        }
+
+        if !self.pending.is_empty() {
+            let trailing = token.line == self.last_line;
+            if trailing {
+                self.flush();
+                let last = self.lines.last_mut().unwrap();
+                last.push(' ');
+                last.push_str(&token.lexeme);
+                self.continued = true;
+                return;
+            }
+            self.flush();
+            self.continued = true;
+        }
+
+        let indent = INDENT.repeat(self.indent + self.continued as usize);
+        self.lines
+            .push(format!("{}{}", indent, token.lexeme.trim_end()));
+    }
+
+    fn flush(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+
+        let pending = std::mem::take(&mut self.pending);
+        let indent = self.indent + self.continued as usize;
+        if fits(&pending, indent) {
+            self.lines.extend(render(&pending, indent));
+            return;
+        }
+        for (part, depth) in branches(&pending) {
+            self.lines.extend(render(&part, indent + depth));
+        }
+    }
+}
+
+fn end_line(token: &Token) -> u32 {
+    token.line + token.lexeme.matches('\n').count() as u32
+}
+
+fn fits(tokens: &[&Token], indent: usize) -> bool {
+    INDENT.len() * indent + join(tokens).chars().count() <= WIDTH
+}
+
+/// Splits an `if` whose unbraced branches do not fit on one line into the
+/// condition, the `else` and the branches below them one level deeper. An
+/// `else if` stays together, and so does a statement that is no such `if`.
+fn branches<'a>(tokens: &[&'a Token]) -> Vec<(Vec<&'a Token>, usize)> {
+    let whole = vec![(tokens.to_vec(), 0)];
+    if tokens.first().map(|t| t.token_type) != Some(IF) {
+        return whole;
+    }
+
+    // the end of the condition, then the `;` ending the unbraced branch
+    let mut depth = 0;
+    let mut cond = None;
+    let mut end = None;
+    for (i, token) in tokens.iter().enumerate() {
+        match token.token_type {
+            LEFT_PAREN => depth += 1,
+            RIGHT_PAREN => {
+                depth -= 1;
+                if depth == 0 && cond.is_none() {
+                    cond = Some(i);
+                }
+            }
+            // a nested `if` would take the `else`
+            IF if i > 0 && depth == 0 => return whole,
+            SEMICOLON if depth == 0 => {
+                end = Some(i);
+                break;
+            }
+            _ => {}
+        }
+    }
+    let (cond, end) = match (cond, end) {
+        (Some(cond), Some(end)) if tokens.get(end + 1).map(|t| t.token_type) == Some(ELSE) => {
+            (cond, end)
+        }
+        _ => return whole,
+    };
+
+    let mut parts = vec![
+        (tokens[..=cond].to_vec(), 0),
+        (tokens[cond + 1..=end].to_vec(), 1),
+    ];
+    let rest = &tokens[end + 2..];
+    match rest.first().map(|t| t.token_type) {
+        Some(IF) => {
+            let mut chain = branches(rest);
+            chain[0].0.insert(0, tokens[end + 1]);
+            parts.extend(chain);
+        }
+        Some(LEFT_BRACE) | None => parts.push((tokens[end + 1..].to_vec(), 0)),
+        Some(_) => {
+            parts.push((vec![tokens[end + 1]], 0));
+            parts.push((rest.to_vec(), 1));
+        }
+    }
+    parts
+}
+
+/// Lays out one statement line, splitting the first call that has arguments
+/// when the line does not fit.
+fn render(tokens: &[&Token], indent: usize) -> Vec<String> {
+    let flat = join(tokens);
+    let prefix = INDENT.repeat(indent);
+    if prefix.len() + flat.chars().count() <= WIDTH || flat.contains('\n') {
+        return vec![prefix + &flat];
+    }
+
+    let (open, close) = match split_point(tokens) {
+        Some(split) => split,
+        None => return vec![prefix + &flat],
+    };
+
+    let mut lines = vec![prefix + &join(&tokens[..=open])];
+
+    let mut args: Vec<&[&Token]> = Vec::new();
+    let mut depth = 0;
+    let mut start = open + 1;
+    for i in open + 1..close {
+        match tokens[i].token_type {
+            LEFT_PAREN | LEFT_BRACKET => depth += 1,
+            RIGHT_PAREN | RIGHT_BRACKET => depth -= 1,
+            COMMA if depth == 0 => {
+                args.push(&tokens[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    args.push(&tokens[start..close]);
+
+    let count = args.len();
+    for (n, arg) in args.into_iter().enumerate() {
+        let mut arg_lines = render(arg, indent + 1);
+        if n + 1 < count {
+            arg_lines.last_mut().unwrap().push(',');
+        }
+        lines.extend(arg_lines);
+    }
+
+    lines.extend(render(&tokens[close..], indent));
+    lines
+}
+
+/// The first call or parameter list with contents, outside of string
+/// interpolations, as the positions of its parentheses.
+fn split_point(tokens: &[&Token]) -> Option<(usize, usize)> {
+    let mut interpolating = 0;
+
+    for (i, token) in tokens.iter().enumerate() {
+        if token.token_type == STRING && token.lexeme.starts_with('}') {
+            interpolating -= 1;
+        }
+        if token.token_type == INTERPOLATION {
+            if !token.lexeme.starts_with('}') {
+                interpolating += 1;
+            }
+            continue;
+        }
+
+        let call = i > 0 && matches!(tokens[i - 1].token_type, IDENTIFIER | RIGHT_PAREN);
+        if interpolating > 0 || token.token_type != LEFT_PAREN || !call {
+            continue;
+        }
+
+        let mut depth = 0;
+        for (j, t) in tokens.iter().enumerate().skip(i) {
+            match t.token_type {
+                LEFT_PAREN => depth += 1,
+                RIGHT_PAREN => depth -= 1,
+                _ => continue,
+            }
+            if depth == 0 {
+                if j > i + 1 {
+                    return Some((i, j));
+                }
+                break;
+            }
+        }
+    }
+    None
+}
+
+fn join(tokens: &[&Token]) -> String {
+    let mut out = String::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if i > 0 && spaced(tokens.get(i.wrapping_sub(2)).copied(), tokens[i - 1], token) {
+            out.push(' ');
+        }
+        out.push_str(token.lexeme.trim_end());
+    }
+    out
+}
+
+/// Whether the token before `prev` leaves `prev` in operand position, where
+/// `-`, `!` and `++` are prefix operators.
+fn operand_expected(prev: Option<&Token>) -> bool {
+    match prev {
+        None => true,
+        Some(t) => !matches!(
+            t.token_type,
+            IDENTIFIER | NUMBER | STRING | TRUE | FALSE | NIL | THIS | RIGHT_PAREN | RIGHT_BRACKET
+        ),
+    }
+}
+
+/// Whether a space goes between `prev` and `next`; `before` precedes `prev`.
+fn spaced(before: Option<&Token>, prev: &Token, next: &Token) -> bool {
+    let interpolation_end =
+        matches!(next.token_type, STRING | INTERPOLATION) && next.lexeme.starts_with('}');
+
+    match (prev.token_type, next.token_type) {
+        (INTERPOLATION, _) | (LEFT_BRACE, RIGHT_BRACE) => false,
+        _ if interpolation_end => false,
+        (LEFT_PAREN | LEFT_BRACKET | DOT | QUESTION_DOT | ELLIPSIS, _) => false,
+        (_, RIGHT_PAREN | RIGHT_BRACKET | COMMA | SEMICOLON | DOT | QUESTION_DOT) => false,
+        (IDENTIFIER | RIGHT_PAREN | RIGHT_BRACKET | STRING, LEFT_PAREN | LEFT_BRACKET) => false,
+        (IDENTIFIER | RIGHT_PAREN | RIGHT_BRACKET, PLUS_PLUS | MINUS_MINUS) => false,
+        // named arguments, as in `f(a: 1)`
+        (IDENTIFIER, COLON) if matches!(before.map(|t| t.token_type), Some(LEFT_PAREN | COMMA)) => {
+            false
+        }
+        (MINUS | BANG | TILDE | PLUS_PLUS | MINUS_MINUS, _) if operand_expected(before) => false,
+        _ => true,
+    }
+}
@@ -1,8 +1,9 @@
-use std::{borrow::Borrow, rc::Rc};
+use std::{fmt, rc::Rc};
 
 use crate::{
     env::Env,
     error::{Error, ResultMSG},
+    expr::Expr,
     interpreter::Interpreter,
     object::Object,
     stmt::Stmt,
@@ -10,44 +11,250 @@ use crate::{
 };
 
 pub trait Callable {
-    fn call(&self, int: &mut Interpreter, args: &[Object]) -> ResultMSG<Object>;
-    fn arity(&self) -> usize;
+    /// Calls with arguments already bound by `signature().bind()`: one slot
+    /// per parameter, `None` where the parameter's default applies.
+    fn call(&self, int: &mut Interpreter, args: &[Option<Object>]) -> ResultMSG<Object>;
+    fn name(&self) -> &str;
+    fn signature(&self) -> &Signature;
+}
+
+pub struct Param {
+    pub name: String,
+    pub default: Option<Expr>,
+    pub rest: bool,
+    /// Where the parameter is declared, zero for natives.
+    pub line: u32,
+    pub column: u32,
+}
+
+impl Param {
+    pub fn required(name: &str) -> Self {
+        Param {
+            name: name.to_string(),
+            default: None,
+            rest: false,
+            line: 0,
+            column: 0,
+        }
+    }
+}
+
+/// The parameter list of a callable. Required parameters come first, then
+/// parameters with defaults, then at most one rest parameter.
+pub struct Signature {
+    pub params: Vec<Param>,
+}
+
+impl Signature {
+    pub fn new(params: Vec<Param>) -> Self {
+        Signature { params }
+    }
+
+    /// Matches positional and named arguments to parameters, collecting
+    /// surplus positional arguments into the rest parameter.
+    pub fn bind(
+        &self,
+        positional: Vec<Object>,
+        named: Vec<(String, Object)>,
+    ) -> Result<Vec<Option<Object>>, String> {
+        let mut slots: Vec<Option<Object>> = Vec::with_capacity(self.params.len());
+        let mut positional = positional.into_iter();
+
+        for param in &self.params {
+            if param.rest {
+                slots.push(Some(Object::list(positional.by_ref().collect())));
+            } else {
+                slots.push(positional.next());
+            }
+        }
+
+        let extra = positional.count();
+        if extra > 0 {
+            return Err(format!(
+                "expected at most {} arguments but got {}",
+                self.params.len(),
+                self.params.len() + extra
+            ));
+        }
+
+        for (name, val) in named {
+            let idx = match self.params.iter().position(|p| p.name == name) {
+                Some(idx) if !self.params[idx].rest => idx,
+                _ => return Err(format!("unknown named argument `{}`", name)),
+            };
+
+            if slots[idx].is_some() {
+                return Err(format!("multiple values for argument `{}`", name));
+            }
+            slots[idx] = Some(val);
+        }
+
+        for (param, slot) in self.params.iter().zip(&slots) {
+            if slot.is_none() && param.default.is_none() {
+                return Err(format!("missing argument `{}`", param.name));
+            }
+        }
+
+        Ok(slots)
+    }
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let params: Vec<String> = self
+            .params
+            .iter()
+            .map(|p| match p {
+                Param { rest: true, .. } => format!("...{}", p.name),
+                Param {
+                    default: Some(_), ..
+                } => format!("{}?", p.name),
+                _ => p.name.clone(),
+            })
+            .collect();
+
+        write!(f, "({})", params.join(", "))
+    }
 }
 
 pub struct LoxFunction {
+    name: String,
     env: Rc<Env>,
-    params: Vec<String>,
+    signature: Rc<Signature>,
     body: Rc<Stmt>,
 }
 
 impl LoxFunction {
-    pub fn new(env: Rc<Env>, params: &[String], body: Rc<Stmt>) -> Rc<LoxFunction> {
+    pub fn new(
+        name: &str,
+        env: Rc<Env>,
+        signature: Rc<Signature>,
+        body: Rc<Stmt>,
+    ) -> Rc<LoxFunction> {
         Rc::new(LoxFunction {
+            name: name.to_string(),
             env,
-            params: params.to_vec(),
+            signature,
             body,
         })
     }
 }
 
 impl Callable for LoxFunction {
-    fn arity(&self) -> usize {
-        self.params.len()
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
     }
 
-    fn call(&self, int: &mut Interpreter, args: &[Object]) -> ResultMSG<Object> {
+    fn call(&self, int: &mut Interpreter, args: &[Option<Object>]) -> ResultMSG<Object> {
         let env = Env::with_parent(self.env.clone());
-        let params = &self.params;
-        let zip = params.into_iter().zip(args.into_iter());
+        let mut scope = int.with_env(env.clone());
 
-        for (param, arg) in zip {
-            env.define(param, arg.clone());
+        let mut bound = Vec::with_capacity(args.len());
+        for (param, arg) in self.signature.params.iter().zip(args) {
+            let val = match (arg, &param.default) {
+                (Some(val), _) => val.clone(),
+                (None, Some(default)) => scope.evaluate(default)?,
+                (None, None) => Object::Literal(Literal::None),
+            };
+            env.define(&param.name, val.clone())?;
+            bound.push((param.name.clone(), val));
         }
 
-        match Interpreter::with_env(env).interpret(self.body.borrow()) {
+        int.hook(|h| h.enter(&self.name, &self.body, &bound));
+        let res = match scope.interpret(&self.body) {
             Ok(()) => Ok(Object::Literal(Literal::None)),
             Err(Error::Return(_, res)) => Ok(res),
             Err(e) => Err(e),
-        }
+        };
+        int.hook(|h| h.exit(&self.name, &res));
+        res
+    }
+}
+
+pub struct NativeFunction {
+    name: &'static str,
+    signature: Signature,
+    func: fn(&mut Interpreter, &[Object]) -> Result<Object, String>,
+}
+
+impl NativeFunction {
+    pub fn new(
+        name: &'static str,
+        params: &[&str],
+        func: fn(&mut Interpreter, &[Object]) -> Result<Object, String>,
+    ) -> Rc<NativeFunction> {
+        Rc::new(NativeFunction {
+            name,
+            signature: Signature::new(params.iter().map(|p| Param::required(p)).collect()),
+            func,
+        })
+    }
+}
+
+impl Callable for NativeFunction {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn call(&self, int: &mut Interpreter, args: &[Option<Object>]) -> ResultMSG<Object> {
+        let args: Vec<Object> = args.iter().flatten().cloned().collect();
+
+        (self.func)(int, &args).map_err(|msg| Error::Runtime(0, msg, self.name.to_string()))
+    }
+}
+
+pub fn len(_int: &mut Interpreter, args: &[Object]) -> Result<Object, String> {
+    let n = match &args[0] {
+        Object::Literal(Literal::StringLit(s)) => s.chars().count(),
+        Object::List(l) => l.borrow().len(),
+        x => return Err(format!("cannot take the length of {:?}", x)),
+    };
+
+    Ok(Object::Literal(Literal::Number(n as f64)))
+}
+
+pub fn assert(int: &mut Interpreter, args: &[Object]) -> Result<Object, String> {
+    match int.is_truthy(&args[0]) {
+        true => Ok(Object::Literal(Literal::None)),
+        false => Err(format!("assertion failed, got {}", args[0].repr())),
+    }
+}
+
+pub fn assert_eq(_int: &mut Interpreter, args: &[Object]) -> Result<Object, String> {
+    match args[0] == args[1] {
+        true => Ok(Object::Literal(Literal::None)),
+        false => Err(format!(
+            "expected {} but got {}",
+            args[1].repr(),
+            args[0].repr()
+        )),
+    }
+}
+
+/// Calls `f` without arguments and fails unless it raises an error,
+/// returning the error's message.
+pub fn assert_throws(int: &mut Interpreter, args: &[Object]) -> Result<Object, String> {
+    let f = match &args[0] {
+        Object::Func(f) => f.clone(),
+        x => return Err(format!("expected a function but got {}", x.repr())),
+    };
+    let bound = f.signature().bind(Vec::new(), Vec::new())?;
+
+    match f.call(int, &bound) {
+        Err(Error::Runtime(_, msg, _)) => Ok(Object::Literal(Literal::StringLit(msg))),
+        Err(e) => Ok(Object::Literal(Literal::StringLit(e.to_string()))),
+        Ok(value) => Err(format!(
+            "expected {} to raise an error but it returned {}",
+            f.name(),
+            value.repr()
+        )),
     }
 }
@@ -0,0 +1,77 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{env::Env, error::ResultMSG, expr::Expr, object::Object, stmt::Stmt, token::Token};
+
+/// Callbacks from a running program, for tools that watch or steer it such
+/// as debuggers. Every method does nothing unless overridden.
+pub trait Hooks {
+    /// Before each statement other than a block runs, with the scope it
+    /// runs in. Returning an error stops the program with it.
+    fn statement(&mut self, _stmt: &Stmt, _env: &Rc<Env>) -> ResultMSG<()> {
+        Ok(())
+    }
+
+    /// When a condition decides which way the program goes, with whether it
+    /// held: the condition of an `if`, a `while` or a `?:`, or the left side
+    /// of `and`, `or` and `??`, which holds when it is not nil.
+    fn branch(&mut self, _cond: &Expr, _held: bool) {}
+
+    /// After a variable is assigned, with its value before and after.
+    fn assign(&mut self, _name: &Token, _old: &Object, _new: &Object) {}
+
+    /// When a Lox function is entered, with the body of its declaration and
+    /// its parameters bound.
+    fn enter(&mut self, _name: &str, _body: &Stmt, _args: &[(String, Object)]) {}
+
+    /// When a Lox function is left, with what it returned or the error that
+    /// ended it.
+    fn exit(&mut self, _name: &str, _result: &ResultMSG<Object>) {}
+
+    /// Takes the text of a `print`, returning whether it was handled; if not
+    /// it goes to stdout.
+    fn print(&mut self, _text: &str) -> bool {
+        false
+    }
+}
+
+/// Several hooks watching the same program, called in order. The first
+/// statement error stops the program and a print goes to the first hook
+/// that handles it.
+pub struct Chain(pub Vec<Rc<RefCell<dyn Hooks>>>);
+
+impl Hooks for Chain {
+    fn statement(&mut self, stmt: &Stmt, env: &Rc<Env>) -> ResultMSG<()> {
+        for hooks in &self.0 {
+            hooks.borrow_mut().statement(stmt, env)?;
+        }
+        Ok(())
+    }
+
+    fn branch(&mut self, cond: &Expr, held: bool) {
+        for hooks in &self.0 {
+            hooks.borrow_mut().branch(cond, held);
+        }
+    }
+
+    fn assign(&mut self, name: &Token, old: &Object, new: &Object) {
+        for hooks in &self.0 {
+            hooks.borrow_mut().assign(name, old, new);
+        }
+    }
+
+    fn enter(&mut self, name: &str, body: &Stmt, args: &[(String, Object)]) {
+        for hooks in &self.0 {
+            hooks.borrow_mut().enter(name, body, args);
+        }
+    }
+
+    fn exit(&mut self, name: &str, result: &ResultMSG<Object>) {
+        for hooks in &self.0 {
+            hooks.borrow_mut().exit(name, result);
+        }
+    }
+
+    fn print(&mut self, text: &str) -> bool {
+        self.0.iter().any(|hooks| hooks.borrow_mut().print(text))
+    }
+}
@@ -1,10 +1,11 @@
-use std::{ops::Deref, rc::Rc};
+use std::{cell::RefCell, ops::Deref, rc::Rc};
 
 use crate::{
     env::Env,
     error::{Error, ResultMSG},
     expr::{Expr, Visitor as ExprVisitor},
-    function::LoxFunction,
+    function::{self, Callable, LoxFunction, NativeFunction, Signature},
+    hooks::Hooks,
     object::Object,
     stmt::{Stmt, Visitor as StmtVisitor},
     token::{Literal, Token, TokenType},
@@ -13,18 +14,61 @@ use crate::{
 pub struct Interpreter {
     pub env: Rc<Env>,
     repl: bool,
+    compat: bool,
+    hooks: Option<Rc<RefCell<dyn Hooks>>>,
 }
 
 impl Interpreter {
     pub fn new(repl: bool) -> Self {
+        let env = Env::new(None);
+        Interpreter::define_natives(&env);
+
         Interpreter {
-            env: Env::new(None),
+            env,
             repl,
+            compat: false,
+            hooks: None,
+        }
+    }
+
+    fn define_natives(env: &Env) {
+        let natives: Vec<Rc<dyn Callable>> =
+            vec![NativeFunction::new("len", &["x"], function::len)];
+
+        for native in natives {
+            let _ = env.define_builtin(native.name(), Object::Func(native.clone()));
         }
     }
 
-    pub fn with_env(env: Rc<Env>) -> Self {
-        Interpreter { env, repl: false }
+    /// Enables the pre-spec semantics where `0` and `""` are falsey and
+    /// `and`/`or` always produce a boolean instead of an operand.
+    pub fn compat(mut self, compat: bool) -> Self {
+        self.compat = compat;
+        self
+    }
+
+    /// Reports execution to `hooks`, here and in every function and block
+    /// this interpreter runs.
+    pub fn hooks(mut self, hooks: Rc<RefCell<dyn Hooks>>) -> Self {
+        self.hooks = Some(hooks);
+        self
+    }
+
+    /// Calls the hooks, if any. A hook that runs Lox code itself, like a
+    /// debugger evaluating an expression, is not told about that code.
+    pub(crate) fn hook<T>(&self, f: impl FnOnce(&mut dyn Hooks) -> T) -> Option<T> {
+        let hooks = self.hooks.as_ref()?;
+        let mut hooks = hooks.try_borrow_mut().ok()?;
+        Some(f(&mut *hooks))
+    }
+
+    pub fn with_env(&self, env: Rc<Env>) -> Self {
+        Interpreter {
+            env,
+            repl: false,
+            compat: self.compat,
+            hooks: self.hooks.clone(),
+        }
     }
 
     pub fn interpret(&mut self, s: &Stmt) -> ResultMSG<()> {
@@ -35,12 +79,38 @@ impl Interpreter {
         Interpreter {
             env: Env::with_parent(self.env.clone()),
             repl: self.repl,
+            compat: self.compat,
+            hooks: self.hooks.clone(),
         }
     }
 
     pub fn evaluate(&mut self, expr: &Expr) -> ResultMSG<Object> {
         self.visit_grouping(expr, expr)
     }
+
+    /// Assigns `val` to the variable `n`, telling the hooks what changed.
+    fn assign(&mut self, n: &Token, val: Object) -> ResultMSG<Object> {
+        let old = match self.hooks {
+            Some(_) => self.env.get(&n.lexeme).ok(),
+            None => None,
+        };
+        let val = self
+            .env
+            .assign(&n.lexeme, val)
+            .map_err(|e| e.at_line(n.line))?;
+        if let Some(old) = old {
+            self.hook(|h| h.assign(n, &old, &val));
+        }
+        Ok(val)
+    }
+
+    pub(crate) fn is_truthy(&self, obj: &Object) -> bool {
+        if self.compat {
+            obj.is_truthy_compat()
+        } else {
+            obj.is_truthy()
+        }
+    }
 }
 
 impl ExprVisitor<ResultMSG<Object>> for Interpreter {
@@ -52,7 +122,17 @@ impl ExprVisitor<ResultMSG<Object>> for Interpreter {
             Expr::Literal(ref lit) => self.visit_literal(expr, lit),
             Expr::Grouping(ref inside) => self.visit_grouping(expr, inside),
             Expr::Assignment(ref id, ref rhs) => self.visit_assignment(expr, id, rhs),
-            Expr::Call(ref expr, ref parent, ref body) => self.visit_call(expr, parent, body),
+            Expr::Compound(ref id, ref op, ref rhs) => self.visit_compound(expr, id, op, rhs),
+            Expr::Update(ref id, ref op, prefix) => self.visit_update(expr, id, op, prefix),
+            Expr::Call(ref callee, ref paren, ref args, ref named) => {
+                self.visit_call(callee, paren, args, named)
+            }
+            Expr::Index(ref object, ref bracket, ref index) => {
+                self.visit_index(expr, object, bracket, index)
+            }
+            Expr::Interpolation(ref parts) => self.visit_interpolation(expr, parts),
+            Expr::Conditional(ref c, ref t, ref e) => self.visit_conditional(expr, c, t, e),
+            Expr::Get(ref object, ref name, safe) => self.visit_get(expr, object, name, safe),
         }
     }
 
@@ -72,7 +152,14 @@ impl ExprVisitor<ResultMSG<Object>> for Interpreter {
                 Object::Literal(Literal::Number(n)) => Ok(Object::Literal(Literal::Number(-n))),
                 _ => self.err_near("cannot negate non-numeric", op, format!("{:?}", r)),
             },
-            TokenType::BANG => match !r.is_truthy() {
+            TokenType::TILDE => match r {
+                Object::Literal(Literal::Number(n)) => {
+                    let n = self.integer(op, n)?;
+                    Ok(Object::Literal(Literal::Number(!n as f64)))
+                }
+                _ => self.err_near("cannot complement non-numeric", op, format!("{:?}", r)),
+            },
+            TokenType::BANG => match !self.is_truthy(&r) {
                 true => Ok(Object::Literal(Literal::True)),
                 false => Ok(Object::Literal(Literal::False)),
             },
@@ -87,145 +174,114 @@ impl ExprVisitor<ResultMSG<Object>> for Interpreter {
         op: &Token,
         rhs: &Expr,
     ) -> ResultMSG<Object> {
-        use crate::object::Object::Literal as ObjLit;
-        use crate::token::Literal::{Number, StringLit};
-        use std::cmp::Ordering as Ord;
-
-        if op.token_type == TokenType::OR || op.token_type == TokenType::AND {
+        if op.in_types(vec![
+            TokenType::OR,
+            TokenType::AND,
+            TokenType::QUESTION_QUESTION,
+        ]) {
             return self.visit_logical(lhs, op, rhs);
         }
 
         let l: Object = lhs.accept(self)?;
         let r: Object = rhs.accept(self)?;
 
-        let res: Literal = match op.token_type {
-            TokenType::PLUS => match (l, r) {
-                (ObjLit(Number(ref ln)), ObjLit(Number(ref rn))) => Number(ln + rn),
-                (ObjLit(StringLit(ref ln)), ObjLit(ref r)) => StringLit(format!("{}{:?}", ln, r)),
-                (ObjLit(ref l), ObjLit(StringLit(ref rn))) => StringLit(format!("{:?}{}", l, rn)),
-                (ref l, ref r) => {
-                    return self.err_near(
-                        "cannot add mixed types",
-                        op,
-                        format!("{:?} + {:?}", l, r),
-                    )
-                }
-            },
-            TokenType::MINUS => match (l, r) {
-                (ObjLit(Number(ln)), ObjLit(Number(rn))) => Number(ln - rn),
-                (l, r) => {
-                    return self.err_near(
-                        "cannot subtract non-numerics",
-                        op,
-                        format!("{:?} - {:?}", l, r),
-                    )
-                }
-            },
-            TokenType::STAR => match (l, r) {
-                (ObjLit(Number(ln)), ObjLit(Number(rn))) => Number(ln * rn),
-                (l, r) => {
-                    return self.err_near(
-                        "cannot multiply non-numerics",
-                        op,
-                        format!("{:?} * {:?}", l, r),
-                    )
-                }
-            },
-            TokenType::SLASH => match (l, r) {
-                (ObjLit(Number(ln)), ObjLit(Number(rn))) if rn == 0.0 => {
-                    return self.err_near("divide by zero", op, format!("{:?} / {:?}", ln, rn))
-                }
-                (ObjLit(Number(ln)), ObjLit(Number(rn))) => Number(ln / rn),
-                (l, r) => {
-                    return self.err_near(
-                        "cannot multiply non-numerics",
-                        op,
-                        format!("{:?} * {:?}", l, r),
-                    )
-                }
-            },
-            TokenType::GREATER
-            | TokenType::GREATER_EQUAL
-            | TokenType::LESS
-            | TokenType::LESS_EQUAL => match l.partial_cmp(&r) {
-                Some(Ord::Less) => {
-                    if op.in_types(vec![TokenType::LESS, TokenType::LESS_EQUAL]) {
-                        Literal::True
-                    } else {
-                        Literal::False
-                    }
-                }
-                Some(Ord::Equal) => {
-                    if op.in_types(vec![TokenType::LESS_EQUAL, TokenType::GREATER_EQUAL]) {
+        self.binary(l, op, r)
+    }
+    fn visit_logical(&mut self, lhs: &Expr, op: &Token, rhs: &Expr) -> ResultMSG<Object> {
+        let l: Object = lhs.accept(self)?;
+        let held = match op.token_type {
+            TokenType::QUESTION_QUESTION => !matches!(l, Object::Literal(Literal::None)),
+            _ => self.is_truthy(&l),
+        };
+        self.hook(|h| h.branch(lhs, held));
+
+        if op.token_type == TokenType::QUESTION_QUESTION {
+            return match l {
+                Object::Literal(Literal::None) => rhs.accept(self),
+                _ => Ok(l),
+            };
+        }
+
+        if self.compat {
+            let res: Literal = match op.token_type {
+                TokenType::AND if l.is_truthy_compat() => {
+                    if rhs.accept(self)?.is_truthy_compat() {
                         Literal::True
                     } else {
                         Literal::False
                     }
                 }
-                Some(Ord::Greater) => {
-                    if op.in_types(vec![TokenType::GREATER, TokenType::GREATER_EQUAL]) {
+                TokenType::OR if l.is_truthy_compat() => Literal::True,
+                TokenType::OR => {
+                    if rhs.accept(self)?.is_truthy_compat() {
                         Literal::True
                     } else {
                         Literal::False
                     }
                 }
-                None => {
-                    return self.err_near("cannot compare types", op, format!("{:?} ? {:?}", l, r))
-                }
-            },
-            TokenType::EQUAL_EQUAL => {
-                if l.eq(&r) {
-                    Literal::True
-                } else {
-                    Literal::False
-                }
-            }
-            TokenType::BANG_EQUAL => {
-                if l.ne(&r) {
-                    Literal::True
-                } else {
-                    Literal::False
-                }
-            }
-            _ => return self.err_op("erroneous binary operator", op),
-        };
-
-        Ok(ObjLit(res))
-    }
-    fn visit_logical(&mut self, lhs: &Expr, op: &Token, rhs: &Expr) -> ResultMSG<Object> {
-        let l: Object = lhs.accept(self)?;
+                _ => Literal::False,
+            };
 
-        let res: Literal = match op.token_type {
-            TokenType::AND if l.is_truthy() => {
-                if rhs.accept(self)?.is_truthy() {
-                    Literal::True
-                } else {
-                    Literal::False
-                }
-            }
-            TokenType::OR if l.is_truthy() => Literal::True,
-            TokenType::OR => {
-                if rhs.accept(self)?.is_truthy() {
-                    Literal::True
-                } else {
-                    Literal::False
-                }
-            }
-            _ => Literal::False,
-        };
+            return Ok(Object::Literal(res));
+        }
 
-        Ok(Object::Literal(res))
+        match op.token_type {
+            TokenType::OR if self.is_truthy(&l) => Ok(l),
+            TokenType::AND if !self.is_truthy(&l) => Ok(l),
+            _ => rhs.accept(self),
+        }
     }
 
-    fn visit_identifier(&mut self, expr: &Expr, n: &String) -> ResultMSG<Object> {
-        self.env.get(&n).map(|lit| lit.clone())
+    fn visit_identifier(&mut self, _expr: &Expr, n: &Token) -> ResultMSG<Object> {
+        self.env.get(&n.lexeme).map_err(|e| e.at_line(n.line))
     }
 
-    fn visit_assignment(&mut self, expr: &Expr, n: &String, rhs: &Box<Expr>) -> ResultMSG<Object> {
+    fn visit_assignment(&mut self, _expr: &Expr, n: &Token, rhs: &Box<Expr>) -> ResultMSG<Object> {
         let val = self.evaluate(rhs)?;
-        self.env.assign(&n, val).map(|lit| lit.clone())
+        self.assign(n, val)
+    }
+    fn visit_compound(
+        &mut self,
+        _expr: &Expr,
+        n: &Token,
+        op: &Token,
+        rhs: &Expr,
+    ) -> ResultMSG<Object> {
+        let current = self.visit_identifier(_expr, n)?;
+        let r = self.evaluate(rhs)?;
+        let val = self.binary(current, op, r)?;
+        self.assign(n, val)
     }
-    fn visit_call(&mut self, expr: &Expr, paren: &Token, params: &[Expr]) -> ResultMSG<Object> {
+
+    fn visit_update(
+        &mut self,
+        _expr: &Expr,
+        n: &Token,
+        op: &Token,
+        prefix: bool,
+    ) -> ResultMSG<Object> {
+        let current = match self.visit_identifier(_expr, n)? {
+            Object::Literal(Literal::Number(c)) => c,
+            x => return self.err_near("cannot increment non-numeric", op, format!("{:?}", x)),
+        };
+
+        let updated = match op.token_type {
+            TokenType::PLUS_PLUS => current + 1.0,
+            _ => current - 1.0,
+        };
+        self.assign(n, Object::Literal(Literal::Number(updated)))?;
+
+        let res = if prefix { updated } else { current };
+        Ok(Object::Literal(Literal::Number(res)))
+    }
+
+    fn visit_call(
+        &mut self,
+        expr: &Expr,
+        paren: &Token,
+        params: &[Expr],
+        named: &[(Token, Expr)],
+    ) -> ResultMSG<Object> {
         let callee = match self.evaluate(expr)? {
             Object::Func(c) => c,
             x => {
@@ -237,42 +293,125 @@ impl ExprVisitor<ResultMSG<Object>> for Interpreter {
             }
         };
 
-        if callee.arity() != params.len() {
-            return self.err_near(
-                &format!(
-                    "expected {} arguments but got {}",
-                    callee.arity(),
-                    params.len()
-                ),
-                paren,
-                "".to_string(),
-            );
-        }
-
         let mut args: Vec<Object> = Vec::with_capacity(params.len());
         for param in params {
             args.push(self.evaluate(param)?);
         }
 
-        callee.call(self, &args)
+        let mut named_args: Vec<(String, Object)> = Vec::with_capacity(named.len());
+        for (name, arg) in named {
+            named_args.push((name.lexeme.clone(), self.evaluate(arg)?));
+        }
+
+        let bound = match callee.signature().bind(args, named_args) {
+            Ok(bound) => bound,
+            Err(msg) => {
+                return self.err_near(
+                    &msg,
+                    paren,
+                    format!("{}{}", callee.name(), callee.signature()),
+                )
+            }
+        };
+
+        callee.call(self, &bound).map_err(|e| e.at_line(paren.line))
+    }
+
+    fn visit_index(
+        &mut self,
+        _expr: &Expr,
+        object: &Expr,
+        bracket: &Token,
+        index: &Expr,
+    ) -> ResultMSG<Object> {
+        let object = object.accept(self)?;
+        let index = index.accept(self)?;
+
+        let list = match object {
+            Object::List(ref l) => l.borrow(),
+            x => return self.err_near("can only index lists", bracket, format!("{:?}", x)),
+        };
+
+        match index {
+            Object::Literal(Literal::Number(n))
+                if n.fract() == 0.0 && n >= 0.0 && (n as usize) < list.len() =>
+            {
+                Ok(list[n as usize].clone())
+            }
+            x => self.err_near(
+                &format!("index out of bounds for list of length {}", list.len()),
+                bracket,
+                format!("{:?}", x),
+            ),
+        }
+    }
+
+    fn visit_conditional(
+        &mut self,
+        _expr: &Expr,
+        cond: &Expr,
+        then_expr: &Expr,
+        else_expr: &Expr,
+    ) -> ResultMSG<Object> {
+        let c = cond.accept(self)?;
+        let held = self.is_truthy(&c);
+        self.hook(|h| h.branch(cond, held));
+
+        if held {
+            then_expr.accept(self)
+        } else {
+            else_expr.accept(self)
+        }
+    }
+
+    fn visit_get(
+        &mut self,
+        _expr: &Expr,
+        object: &Expr,
+        name: &Token,
+        safe: bool,
+    ) -> ResultMSG<Object> {
+        match object.accept(self)? {
+            Object::Literal(Literal::None) if safe => Ok(Object::Literal(Literal::None)),
+            x => self.err_near(
+                &format!("undefined property `{}`", name.lexeme),
+                name,
+                format!("{:?}", x),
+            ),
+        }
+    }
+
+    fn visit_interpolation(&mut self, _expr: &Expr, parts: &[Expr]) -> ResultMSG<Object> {
+        let mut s = String::new();
+        for part in parts {
+            s.push_str(&part.accept(self)?.to_string());
+        }
+
+        Ok(Object::Literal(Literal::StringLit(s)))
     }
 }
 
 impl StmtVisitor<ResultMSG<()>> for Interpreter {
     fn visit_stmt(&mut self, s: &Stmt) -> ResultMSG<()> {
+        if !matches!(s, Stmt::Empty | Stmt::Block(_) | Stmt::Doc(..)) {
+            self.hook(|h| h.statement(s, &self.env)).unwrap_or(Ok(()))?;
+        }
+
         match *s {
             Stmt::Empty => Ok(()),
             Stmt::Print(ref e) => self.visit_print_stmt(e),
             Stmt::Expression(ref e) => self.visit_expression_stmt(e),
             Stmt::Block(ref ss) => self.visit_block_stmt(ss),
             Stmt::Declaration(ref n, ref e) => self.visit_declaration_stmt(n, e.as_ref()),
+            Stmt::Const(ref n, ref e) => self.visit_const_stmt(n, e),
             Stmt::If(ref c, ref t, ref e) => {
                 self.visit_if(c, t.as_ref(), e.as_ref().map(|x| x.deref()))
             }
             Stmt::While(ref e, ref b) => self.visit_while(e, b.deref()),
             Stmt::Break(l) => self.visit_break(l),
             Stmt::Return(l, ref e) => self.visit_return(l, e),
-            Stmt::Function(ref n, ref p, ref b) => self.visit_function(n.to_owned(), p, b.clone()),
+            Stmt::Function(ref n, ref s, ref b) => self.visit_function(n, s.clone(), b.clone()),
+            Stmt::Doc(_, ref s) => self.visit_stmt(s),
         }
     }
 
@@ -285,14 +424,26 @@ impl StmtVisitor<ResultMSG<()>> for Interpreter {
     }
 
     fn visit_print_stmt(&mut self, expression: &Expr) -> ResultMSG<()> {
-        println!("{:?}", expression.accept(self)?);
+        let text = expression.accept(self)?.to_string();
+        if self.hook(|h| h.print(&text)) != Some(true) {
+            println!("{}", text);
+        }
         Ok(())
     }
 
-    fn visit_declaration_stmt(&mut self, name: &String, init: Option<&Expr>) -> ResultMSG<()> {
+    fn visit_declaration_stmt(&mut self, name: &Token, init: Option<&Expr>) -> ResultMSG<()> {
         let val: Object =
             init.map_or_else(|| Ok(Object::Literal(Literal::None)), |e| e.accept(self))?;
-        self.env.define(&name, val)
+        self.env
+            .define(&name.lexeme, val)
+            .map_err(|e| e.at_line(name.line))
+    }
+
+    fn visit_const_stmt(&mut self, name: &Token, init: &Expr) -> ResultMSG<()> {
+        let val: Object = init.accept(self)?;
+        self.env
+            .define_const(&name.lexeme, val)
+            .map_err(|e| e.at_line(name.line))
     }
 
     fn visit_block_stmt(&mut self, statements: &Vec<Stmt>) -> ResultMSG<()> {
@@ -310,8 +461,10 @@ impl StmtVisitor<ResultMSG<()>> for Interpreter {
         else_stmt: Option<&Stmt>,
     ) -> ResultMSG<()> {
         let cond = expr.accept(self)?;
+        let held = self.is_truthy(&cond);
+        self.hook(|h| h.branch(expr, held));
 
-        if cond.is_truthy() {
+        if held {
             return then_stmt.accept(self);
         }
 
@@ -323,7 +476,14 @@ impl StmtVisitor<ResultMSG<()>> for Interpreter {
     }
 
     fn visit_while(&mut self, expr: &Expr, body: &Stmt) -> ResultMSG<()> {
-        while self.evaluate(expr)?.is_truthy() {
+        loop {
+            let cond = self.evaluate(expr)?;
+            let held = self.is_truthy(&cond);
+            self.hook(|h| h.branch(expr, held));
+            if !held {
+                break;
+            }
+
             match body.accept(self) {
                 Err(Error::Break(_)) => break,
                 Err(e) => return Err(e),
@@ -338,11 +498,23 @@ impl StmtVisitor<ResultMSG<()>> for Interpreter {
         Err(Error::Break(line))
     }
 
-    fn visit_function(&mut self, name: String, params: &[String], body: Rc<Stmt>) -> ResultMSG<()> {
-        self.env.define(
-            &name,
-            Object::Func(LoxFunction::new(self.env.clone(), params, body)),
-        )
+    fn visit_function(
+        &mut self,
+        name: &Token,
+        signature: Rc<Signature>,
+        body: Rc<Stmt>,
+    ) -> ResultMSG<()> {
+        self.env
+            .define(
+                &name.lexeme,
+                Object::Func(LoxFunction::new(
+                    &name.lexeme,
+                    self.env.clone(),
+                    signature,
+                    body,
+                )),
+            )
+            .map_err(|e| e.at_line(name.line))
     }
 
     fn visit_return(&mut self, line: u64, expr: &Expr) -> ResultMSG<()> {
@@ -352,6 +524,169 @@ impl StmtVisitor<ResultMSG<()>> for Interpreter {
 }
 
 impl Interpreter {
+    /// Applies a binary operator to already evaluated operands, shared by
+    /// plain binary expressions and compound assignments.
+    fn binary(&self, l: Object, op: &Token, r: Object) -> ResultMSG<Object> {
+        use crate::object::Object::Literal as ObjLit;
+        use crate::token::Literal::{Number, StringLit};
+        use std::cmp::Ordering as Ord;
+
+        let res: Literal = match op.token_type {
+            TokenType::PLUS => match (l, r) {
+                (ObjLit(Number(ref ln)), ObjLit(Number(ref rn))) => Number(ln + rn),
+                (ObjLit(StringLit(ref ln)), ObjLit(ref r)) => StringLit(format!("{}{}", ln, r)),
+                (ObjLit(ref l), ObjLit(StringLit(ref rn))) => StringLit(format!("{}{}", l, rn)),
+                (ref l, ref r) => {
+                    return self.err_near(
+                        "cannot add mixed types",
+                        op,
+                        format!("{:?} + {:?}", l, r),
+                    )
+                }
+            },
+            TokenType::MINUS => match (l, r) {
+                (ObjLit(Number(ln)), ObjLit(Number(rn))) => Number(ln - rn),
+                (l, r) => {
+                    return self.err_near(
+                        "cannot subtract non-numerics",
+                        op,
+                        format!("{:?} - {:?}", l, r),
+                    )
+                }
+            },
+            TokenType::STAR => match (l, r) {
+                (ObjLit(Number(ln)), ObjLit(Number(rn))) => Number(ln * rn),
+                (l, r) => {
+                    return self.err_near(
+                        "cannot multiply non-numerics",
+                        op,
+                        format!("{:?} * {:?}", l, r),
+                    )
+                }
+            },
+            TokenType::SLASH => match (l, r) {
+                (ObjLit(Number(ln)), ObjLit(Number(rn))) if rn == 0.0 => {
+                    return self.err_near("divide by zero", op, format!("{:?} / {:?}", ln, rn))
+                }
+                (ObjLit(Number(ln)), ObjLit(Number(rn))) => Number(ln / rn),
+                (l, r) => {
+                    return self.err_near(
+                        "cannot multiply non-numerics",
+                        op,
+                        format!("{:?} * {:?}", l, r),
+                    )
+                }
+            },
+            TokenType::PERCENT | TokenType::TILDE_SLASH => match (l, r) {
+                (ObjLit(Number(ln)), ObjLit(Number(rn))) if rn == 0.0 => {
+                    return self.err_near(
+                        "divide by zero",
+                        op,
+                        format!("{:?} {} {:?}", ln, op.lexeme, rn),
+                    )
+                }
+                (ObjLit(Number(ln)), ObjLit(Number(rn))) if op.token_type == TokenType::PERCENT => {
+                    Number(ln % rn)
+                }
+                (ObjLit(Number(ln)), ObjLit(Number(rn))) => Number((ln / rn).trunc()),
+                (l, r) => {
+                    let msg = match op.token_type {
+                        TokenType::PERCENT => "cannot take modulo of non-numerics",
+                        _ => "cannot integer-divide non-numerics",
+                    };
+                    return self.err_near(msg, op, format!("{:?} {} {:?}", l, op.lexeme, r));
+                }
+            },
+            TokenType::STAR_STAR => match (l, r) {
+                (ObjLit(Number(ln)), ObjLit(Number(rn))) => Number(ln.powf(rn)),
+                (l, r) => {
+                    return self.err_near(
+                        "cannot exponentiate non-numerics",
+                        op,
+                        format!("{:?} ** {:?}", l, r),
+                    )
+                }
+            },
+            TokenType::AMPERSAND
+            | TokenType::PIPE
+            | TokenType::CARET
+            | TokenType::LESS_LESS
+            | TokenType::GREATER_GREATER => match (l, r) {
+                (ObjLit(Number(ln)), ObjLit(Number(rn))) => {
+                    let (ln, rn) = (self.integer(op, ln)?, self.integer(op, rn)?);
+                    let res = match op.token_type {
+                        TokenType::AMPERSAND => ln & rn,
+                        TokenType::PIPE => ln | rn,
+                        TokenType::CARET => ln ^ rn,
+                        _ if !(0..64).contains(&rn) => {
+                            return self.err_near(
+                                "shift amount must be between 0 and 63",
+                                op,
+                                format!("{}", rn),
+                            )
+                        }
+                        TokenType::LESS_LESS => ln << rn,
+                        _ => ln >> rn,
+                    };
+                    Number(res as f64)
+                }
+                (l, r) => {
+                    return self.err_near(
+                        "bitwise operators need numeric operands",
+                        op,
+                        format!("{:?} {} {:?}", l, op.lexeme, r),
+                    )
+                }
+            },
+            TokenType::GREATER
+            | TokenType::GREATER_EQUAL
+            | TokenType::LESS
+            | TokenType::LESS_EQUAL => match l.partial_cmp(&r) {
+                Some(Ord::Less) => {
+                    if op.in_types(vec![TokenType::LESS, TokenType::LESS_EQUAL]) {
+                        Literal::True
+                    } else {
+                        Literal::False
+                    }
+                }
+                Some(Ord::Equal) => {
+                    if op.in_types(vec![TokenType::LESS_EQUAL, TokenType::GREATER_EQUAL]) {
+                        Literal::True
+                    } else {
+                        Literal::False
+                    }
+                }
+                Some(Ord::Greater) => {
+                    if op.in_types(vec![TokenType::GREATER, TokenType::GREATER_EQUAL]) {
+                        Literal::True
+                    } else {
+                        Literal::False
+                    }
+                }
+                None => {
+                    return self.err_near("cannot compare types", op, format!("{:?} ? {:?}", l, r))
+                }
+            },
+            TokenType::EQUAL_EQUAL => {
+                if l.eq(&r) {
+                    Literal::True
+                } else {
+                    Literal::False
+                }
+            }
+            TokenType::BANG_EQUAL => {
+                if l.ne(&r) {
+                    Literal::True
+                } else {
+                    Literal::False
+                }
+            }
+            _ => return self.err_op("erroneous binary operator", op),
+        };
+
+        Ok(ObjLit(res))
+    }
+
     fn err_op(&self, msg: &str, op: &Token) -> ResultMSG<Object> {
         Err(Error::Runtime(
             op.line,
@@ -363,4 +698,18 @@ impl Interpreter {
     fn err_near(&self, msg: &str, op: &Token, near: String) -> ResultMSG<Object> {
         Err(Error::Runtime(op.line, msg.to_string(), near))
     }
+
+    /// Bitwise operators work on the 64-bit integer a number represents and
+    /// reject fractional or out-of-range values rather than truncating them.
+    fn integer(&self, op: &Token, n: f64) -> ResultMSG<i64> {
+        if n.fract() != 0.0 || n < i64::MIN as f64 || n >= i64::MAX as f64 {
+            return Err(Error::Runtime(
+                op.line,
+                "bitwise operators need integer operands".to_string(),
+                format!("{}", n),
+            ));
+        }
+
+        Ok(n as i64)
+    }
 }
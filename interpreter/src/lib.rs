@@ -1,106 +1,1631 @@
 use std::{
-    fs::File,
-    io::{self, stderr, BufRead, BufReader, Read, Write},
+    cell::RefCell,
+    fs,
+    io::{self, stderr, Read, Write},
+    rc::Rc,
 };
 
-use error::ResultMSG;
+use coverage::Coverage;
+use error::{Error, ResultMSG};
+use hooks::{Chain, Hooks};
+use json::Json;
+use object::Object;
+use profile::Profiler;
 use scanner::{Scanner, StmtIterator};
+use stmt::Stmt;
+use token::Literal;
+use trace::Tracer;
 
-use crate::{interpreter::Interpreter, parser::Parser};
+use crate::{interpreter::Interpreter, repl::Repl, resolver::Resolver};
 
+mod coverage;
+mod dap;
+mod debug;
+mod debugger;
+mod editor;
 mod env;
 mod error;
 mod expr;
+mod formatter;
 mod function;
+mod hooks;
 mod interpreter;
+mod json;
+mod lint;
+mod lsp;
 mod object;
 mod parser;
+mod printer;
+mod profile;
+mod repl;
+mod resolver;
 mod scanner;
 mod stmt;
+mod symbols;
+mod testing;
 mod token;
+mod trace;
 
-pub fn run_file(path: String) {
-    let mut file = match File::open(path.clone()) {
-        Err(e) => panic!("Could not open file {}\n{}", path, e),
-        Ok(file) => file,
-    };
+/// Exit statuses, following the conventions of `sysexits.h`.
+pub const EX_USAGE: i32 = 64;
+pub const EX_DATAERR: i32 = 65;
+pub const EX_SOFTWARE: i32 = 70;
+pub const EX_IOERR: i32 = 74;
+
+/// How scripts are run.
+#[derive(Default)]
+pub struct Options {
+    pub compat: bool,
+    /// Arguments after the script name, visible to it as the global `args`.
+    pub args: Vec<String>,
+    /// Print a profile of the run to stderr.
+    pub profile: bool,
+    /// Write the run's call stacks in the folded format to this file.
+    pub folded: Option<String>,
+    /// Print a coverage summary to stderr and write an lcov file here.
+    pub coverage: Option<String>,
+    /// Log statements, calls and assignments to stderr.
+    pub trace: bool,
+    /// Log them to this file instead.
+    pub trace_to: Option<String>,
+    /// Only log inside calls to these functions.
+    pub trace_functions: Vec<String>,
+}
 
+/// Reads a script, where `-` stands for standard input.
+pub fn read_source(path: &str) -> io::Result<String> {
     let mut code = String::new();
-    match file.read_to_string(&mut code) {
-        Err(e) => panic!("Error reading file {}\n{}", path, e),
-        Ok(_) => (),
+    match path {
+        "-" => io::stdin().read_to_string(&mut code).map(|_| code),
+        _ => fs::read_to_string(path),
     }
+}
 
-    let mut i = Interpreter::new(false);
-
-    match run(&code, &mut i) {
-        Ok(t) => {}
+/// Runs the script at `path` and returns the process exit status.
+pub fn run_file(path: &str, opts: &Options) -> i32 {
+    match read_source(path) {
+        Ok(code) => run_script(path, &code, opts),
         Err(e) => {
-            return;
+            eprintln!("rlox: cannot read {}: {}", path, e);
+            EX_IOERR
         }
     }
 }
 
-pub fn run_prompt() -> io::Result<()> {
-    let stdin = io::stdin();
+/// Runs `code` as a script and returns the process exit status.
+pub fn run_source(code: &str, opts: &Options) -> i32 {
+    run_script("<eval>", code, opts)
+}
+
+/// Runs `code`, called `path` in reports, with the tools `opts` asks for
+/// watching it.
+fn run_script(path: &str, code: &str, opts: &Options) -> i32 {
+    let stmts = match compile(code) {
+        Ok(stmts) => stmts,
+        Err(e) => return exit_code(&e),
+    };
 
-    let mut i = Interpreter::new(true);
-    loop {
-        print!("> ");
-        io::stdout().flush()?;
-        let mut line = String::new();
-        stdin.read_line(&mut line)?;
-        if line.is_empty() || line == "\n" {
+    let mut tools: Vec<Rc<RefCell<dyn Hooks>>> = Vec::new();
+    let profiler = match opts.profile || opts.folded.is_some() {
+        true => Some(Rc::new(RefCell::new(Profiler::new()))),
+        false => None,
+    };
+    if let Some(profiler) = &profiler {
+        profiler.borrow_mut().start();
+        tools.push(profiler.clone());
+    }
+    let coverage = match opts.coverage {
+        Some(_) => Some(Rc::new(RefCell::new(Coverage::new(&stmts)))),
+        None => None,
+    };
+    if let Some(coverage) = &coverage {
+        tools.push(coverage.clone());
+    }
+
+    if opts.trace || opts.trace_to.is_some() || !opts.trace_functions.is_empty() {
+        let out: Box<dyn Write> = match &opts.trace_to {
+            Some(file) => match fs::File::create(file) {
+                Ok(f) => Box::new(io::BufWriter::new(f)),
+                Err(e) => {
+                    eprintln!("rlox: cannot write {}: {}", file, e);
+                    return EX_IOERR;
+                }
+            },
+            None => Box::new(stderr()),
+        };
+        let functions = opts.trace_functions.clone();
+        tools.push(Rc::new(RefCell::new(Tracer::new(code, out, functions))));
+    }
+
+    let mut i = script_interpreter(opts);
+    if !tools.is_empty() {
+        i = i.hooks(Rc::new(RefCell::new(Chain(tools))));
+    }
+    let mut status = 0;
+    for stmt in &stmts {
+        if let Err(e) = i.interpret(stmt) {
+            eprintln!("{}", e);
+            status = exit_code(&e);
             break;
         }
-        match run(&line, &mut i) {
-            Ok(t) => {}
+    }
+
+    let mut outputs: Vec<(&str, String)> = Vec::new();
+    if let Some(profiler) = profiler {
+        let mut profiler = profiler.borrow_mut();
+        profiler.finish();
+        if opts.profile {
+            eprint!("{}", profiler.report(code));
+        }
+        if let Some(folded) = &opts.folded {
+            outputs.push((folded, profiler.folded()));
+        }
+    }
+    if let (Some(coverage), Some(lcov)) = (coverage, &opts.coverage) {
+        let coverage = coverage.borrow();
+        eprint!("{}", coverage.summary(path));
+        outputs.push((lcov, coverage.lcov(path)));
+    }
+
+    for (file, text) in outputs {
+        if let Err(e) = fs::write(file, text) {
+            eprintln!("rlox: cannot write {}: {}", file, e);
+            status = status.max(EX_IOERR);
+        }
+    }
+    status
+}
+
+/// An interpreter for a whole script, with its arguments defined.
+fn script_interpreter(opts: &Options) -> Interpreter {
+    let i = Interpreter::new(false).compat(opts.compat);
+    let args = opts
+        .args
+        .iter()
+        .map(|a| Object::Literal(Literal::StringLit(a.clone())))
+        .collect();
+    let _ = i.env.define_builtin("args", Object::list(args));
+    i
+}
+
+/// Scans, parses and resolves scripts without running them, reporting
+/// every problem found as text or as a JSON array. Returns the process exit
+/// status.
+pub fn check_files(paths: &[String], json: bool) -> i32 {
+    let mut status = 0;
+    let mut found: Vec<Json> = Vec::new();
+
+    for path in paths {
+        let code = match read_source(path) {
+            Ok(code) => code,
             Err(e) => {
-                std::process::exit(65);
+                eprintln!("rlox: cannot read {}: {}", path, e);
+                status = EX_IOERR;
+                continue;
+            }
+        };
+
+        for err in check(&code) {
+            if status == 0 {
+                status = EX_DATAERR;
+            }
+            match json {
+                true => found.push(diagnostic(path, &err)),
+                false => eprintln!("{}: {}", path, err),
             }
         }
     }
-    Ok(())
+
+    if json {
+        println!("{}", Json::Array(found));
+    }
+    status
 }
 
-fn run(code: &str, interpreter: &mut Interpreter) -> ResultMSG<()> {
-    let mut scanner = Scanner::new(code.to_string());
-    let tokens = scanner.scan_tokens();
+/// Every problem in `code` that can be found without running it.
+fn check(code: &str) -> Vec<Error> {
+    let mut globals = Interpreter::new(false).env.names();
+    globals.push("args".to_string());
+    // test files may call the assertions `rlox test` defines
+    globals.extend(testing::ASSERTS.iter().map(|a| a.to_string()));
 
-    for token in &tokens {
-        println!("{:?}", token);
+    match analyze(code, Resolver::new().globals(globals)) {
+        Ok(_) => Vec::new(),
+        Err(errors) => errors,
     }
+}
 
-    for res in scanner.statements() {
-        match res {
+fn diagnostic(path: &str, err: &Error) -> Json {
+    let (kind, line, message, near) = match err {
+        Error::Lexer(l, m, n) => ("lex", *l, m.clone(), n.clone()),
+        Error::Parser(l, m, n) => ("parse", *l, m.clone(), n.clone()),
+        Error::Resolve(l, m, n) => ("resolve", *l, m.clone(), n.clone()),
+        e => ("runtime", 0, e.to_string(), String::new()),
+    };
+
+    Json::object(vec![
+        ("file", Json::str(path)),
+        ("line", Json::Number(line as f64)),
+        ("kind", Json::str(kind)),
+        ("message", Json::String(message)),
+        ("near", Json::String(near)),
+    ])
+}
+
+/// Rewrites scripts in the canonical layout, or with `check` only reports
+/// the ones that are not formatted. `-` formats standard input to standard
+/// output. Returns the process exit status.
+pub fn format_files(paths: &[String], check: bool) -> i32 {
+    let mut status = 0;
+
+    for path in paths {
+        let code = match read_source(path) {
+            Ok(code) => code,
+            Err(e) => {
+                eprintln!("rlox: cannot read {}: {}", path, e);
+                status = EX_IOERR;
+                continue;
+            }
+        };
+
+        let formatted = match formatter::format(&code) {
+            Ok(formatted) => formatted,
             Err(e) => {
-                writeln!(&mut stderr(), "{}", e);
-                break;
+                eprintln!("{}: {}", path, e);
+                status = EX_DATAERR;
+                continue;
+            }
+        };
+
+        if check {
+            if formatted != code {
+                println!("{}: not formatted", path);
+                status = status.max(1);
+            }
+        } else if path == "-" {
+            print!("{}", formatted);
+        } else if formatted != code {
+            if let Err(e) = fs::write(path, formatted) {
+                eprintln!("rlox: cannot write {}: {}", path, e);
+                status = EX_IOERR;
             }
-            Ok(stmt) => interpreter.interpret(&stmt)?,
         }
     }
 
-    /*
-     * let mut parser = Parser::new(tokens);
-    let stmt = parser.parse();
+    status
+}
+
+/// Reports the lint warnings of scripts, with the rules configured by
+/// `config`, or by a `.loxlint` file in the current directory when there is
+/// one. Exits with 1 when there were warnings.
+pub fn lint_files(paths: &[String], config: Option<&str>) -> i32 {
+    let config = match lint_config(config) {
+        Ok(config) => config,
+        Err(status) => return status,
+    };
+    let mut status = 0;
+
+    for path in paths {
+        let code = match read_source(path) {
+            Ok(code) => code,
+            Err(e) => {
+                eprintln!("rlox: cannot read {}: {}", path, e);
+                status = EX_IOERR;
+                continue;
+            }
+        };
+
+        match lint::lint(&code, &config) {
+            Ok(warnings) => {
+                for warning in &warnings {
+                    println!("{}: {}", path, warning);
+                }
+                if !warnings.is_empty() {
+                    status = status.max(1);
+                }
+            }
+            Err(errors) => {
+                for err in errors {
+                    eprintln!("{}: {}", path, err);
+                }
+                status = status.max(EX_DATAERR);
+            }
+        }
+    }
 
-    println!("{:#?}", stmt);
+    status
+}
 
-    if let Ok(stmt) = stmt {
-        let out = interpreter.interpret(&stmt);
+/// Runs the `test_` functions of test files and directories of them,
+/// printing a summary. Exits with 1 when a test failed.
+pub fn test_files(paths: &[String], opts: &Options) -> i32 {
+    testing::run(paths, opts, &mut io::stdout())
+}
 
-        println!("{:?}", out);
+/// Runs the language server on stdin and stdout until the client exits.
+pub fn serve_lsp() -> i32 {
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    match lsp::Server::new().serve(&mut stdin.lock(), &mut stdout) {
+        Ok(status) => status,
+        Err(e) => {
+            eprintln!("rlox: {}", e);
+            EX_IOERR
+        }
+    }
+}
+
+/// Runs the debug adapter on stdin and stdout until the client disconnects.
+pub fn serve_dap() -> i32 {
+    dap::serve(
+        Box::new(io::BufReader::new(io::stdin())),
+        Box::new(io::stdout()),
+    )
+}
+
+/// Debugs a script interactively on stdin and stdout.
+pub fn debug_file(path: &str, opts: &Options) -> i32 {
+    debugger::debug(
+        path,
+        opts,
+        Box::new(io::BufReader::new(io::stdin())),
+        Box::new(io::stdout()),
+    )
+}
+
+fn lint_config(path: Option<&str>) -> Result<lint::Config, i32> {
+    let (path, text) = match path {
+        Some(path) => match fs::read_to_string(path) {
+            Ok(text) => (path, text),
+            Err(e) => {
+                eprintln!("rlox: cannot read {}: {}", path, e);
+                return Err(EX_IOERR);
+            }
+        },
+        None => match fs::read_to_string(lint::CONFIG_FILE) {
+            Ok(text) => (lint::CONFIG_FILE, text),
+            Err(_) => return Ok(lint::Config::default()),
+        },
+    };
+
+    lint::Config::parse(&text).map_err(|e| {
+        eprintln!("rlox: {}: {}", path, e);
+        EX_USAGE
+    })
+}
+
+/// Line `line` of a script split into `lines`, counting from 1. Empty when
+/// there is no such line, as for the line 0 of errors from natives.
+fn source_line<S: AsRef<str>>(lines: &[S], line: u32) -> &str {
+    (line as usize)
+        .checked_sub(1)
+        .and_then(|i| lines.get(i))
+        .map_or("", |l| l.as_ref())
+}
+
+fn exit_code(e: &Error) -> i32 {
+    match e {
+        Error::Lexer(..) | Error::Parser(..) | Error::Resolve(..) => EX_DATAERR,
+        Error::Runtime(..) | Error::Break(_) | Error::Return(..) => EX_SOFTWARE,
+    }
+}
+
+pub fn run_prompt(compat: bool) -> io::Result<()> {
+    Repl::new(compat).run()
+}
+
+/// Scans, parses and resolves `code`, reporting every error found.
+fn compile(code: &str) -> ResultMSG<Vec<Stmt>> {
+    analyze(code, Resolver::new()).map_err(|mut errors| {
+        for err in &errors {
+            eprintln!("{}", err);
+        }
+        errors.remove(0)
+    })
+}
+
+/// Scans and parses `code`, then runs `resolver` over the statements that
+/// parsed, collecting the errors of every phase.
+fn analyze(code: &str, mut resolver: Resolver) -> Result<Vec<Stmt>, Vec<Error>> {
+    let mut scanner = Scanner::new(code.to_string());
+    scanner.scan_tokens();
+
+    // Nothing runs unless the whole entry parses and resolves, so a typo
+    // late in a file cannot leave its first half executed.
+    let mut stmts: Vec<Stmt> = Vec::new();
+    let mut errors: Vec<Error> = std::mem::take(&mut scanner.errors);
+    for res in scanner.statements() {
+        match res {
+            Ok(stmt) => stmts.push(stmt),
+            Err(e) => errors.push(e),
+        }
+    }
+
+    // what failed to parse may have declared names used elsewhere
+    if !errors.is_empty() {
+        resolver = resolver.partial();
+    }
+    for stmt in &stmts {
+        resolver.resolve(stmt);
+    }
+    resolver.finish();
+    errors.extend(resolver.errors);
+
+    match errors.is_empty() {
+        true => Ok(stmts),
+        false => Err(errors),
+    }
+}
+
+fn run(code: &str, interpreter: &mut Interpreter) -> ResultMSG<()> {
+    for stmt in &compile(code)? {
+        if let Err(e) = interpreter.interpret(stmt) {
+            eprintln!("{}", e);
+            return Err(e);
+        }
     }
 
-    */
     return Ok(());
 }
 
 #[cfg(test)]
 pub mod test {
+    use crate::{
+        error::Error,
+        interpreter::Interpreter,
+        repl::Repl,
+        run,
+        scanner::{Scanner, StmtIterator},
+        stmt::Stmt,
+        token::{Literal, TokenType},
+    };
+
+    fn global(code: &str, compat: bool, name: &str) -> String {
+        let mut i = Interpreter::new(false).compat(compat);
+        run(code, &mut i).unwrap();
+        format!("{:?}", i.env.get(name).unwrap())
+    }
+
+    fn fails(code: &str) -> bool {
+        let mut i = Interpreter::new(false);
+        run(code, &mut i).is_err()
+    }
+
     #[test]
     fn test_open() {
         assert_eq!(1, 1)
     }
+
+    #[test]
+    fn test_truthiness() {
+        let code = "var a = 0 and \"zero\"; var b = \"\" or \"empty\"; var c = nil or \"default\"; var d = false and 1;";
+
+        assert_eq!(global(code, false, "a"), "StringLit(\"zero\")");
+        assert_eq!(global(code, false, "b"), "StringLit(\"\")");
+        assert_eq!(global(code, false, "c"), "StringLit(\"default\")");
+        assert_eq!(global(code, false, "d"), "False");
+
+        assert_eq!(global(code, true, "a"), "False");
+        assert_eq!(global(code, true, "b"), "True");
+        assert_eq!(global(code, true, "c"), "True");
+        assert_eq!(global(code, true, "d"), "False");
+
+        let mut scanner = Scanner::new("true false".to_string());
+        let tokens = scanner.scan_tokens();
+        assert_eq!(tokens[0].lexeme, "true");
+        assert_eq!(tokens[1].lexeme, "false");
+    }
+
+    #[test]
+    fn test_block_comments() {
+        let mut scanner = Scanner::new("/* a /* nested\n */ still\n comment */ x /*".to_string());
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(tokens[0].token_type, TokenType::IDENTIFIER);
+        assert_eq!(tokens[0].line, 3);
+        match scanner.errors.as_slice() {
+            [Error::Lexer(3, msg, _)] => assert_eq!(msg, "unterminated comment"),
+            e => panic!("unexpected errors {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_doc_comments() {
+        let mut scanner = Scanner::new("/// Adds one.\n/// Returns a number.\nfun inc(a) { return a + 1; }\n/// dropped\nprint 1;".to_string());
+        scanner.scan_tokens();
+        let stmts: Vec<Stmt> = scanner.statements().map(|s| s.unwrap()).collect();
+
+        assert_eq!(
+            stmts[0].doc_comment(),
+            Some("Adds one.\nReturns a number.".to_string())
+        );
+        assert!(matches!(stmts[1], Stmt::Print(_)));
+    }
+
+    #[test]
+    fn test_string_escapes() {
+        let code = "\"a\\tb\\n\\\"c\\\" \\\\ \\u{1F600}\" r\"C:\\dir\\n\" \"\"\"two\nlines\"\"\" x";
+        let mut scanner = Scanner::new(code.to_string());
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(tokens[0].lexeme, "a\tb\n\"c\" \\ \u{1F600}");
+        assert_eq!(tokens[1].lexeme, "C:\\dir\\n");
+        assert_eq!(tokens[2].lexeme, "two\nlines");
+        assert_eq!(tokens[2].line, 1);
+        assert_eq!(tokens[3].line, 2);
+        assert!(scanner.errors.is_empty());
+
+        let mut scanner = Scanner::new("\"\\q \\u{110000}\"".to_string());
+        scanner.scan_tokens();
+        let msgs: Vec<String> = scanner.errors.iter().map(|e| e.to_string()).collect();
+
+        assert_eq!(msgs.len(), 2);
+        assert!(msgs[0].contains("invalid escape sequence"));
+        assert!(msgs[1].contains("invalid unicode code point"));
+    }
+
+    #[test]
+    fn test_interpolation() {
+        let code = "var name = \"bob\"; var count = 2; var a = \"Hello ${name}, you have ${count + 1} items\"; var b = \"${\"in${ \"ner\" }\" + \"}\"} {x} \\${y}\";";
+
+        assert_eq!(
+            global(code, false, "a"),
+            "StringLit(\"Hello bob, you have 3 items\")"
+        );
+        assert_eq!(global(code, false, "b"), "StringLit(\"inner} {x} ${y}\")");
+    }
+
+    #[test]
+    fn test_number_literals() {
+        let mut scanner =
+            Scanner::new("0x1F 0b1010 0o17 1_000_000 6.02e23 2.5E-3 1_0.2_5".to_string());
+        let nums: Vec<Literal> = scanner
+            .scan_tokens()
+            .into_iter()
+            .map(|t| t.literal)
+            .collect();
+
+        assert_eq!(
+            nums[..7],
+            [31.0, 10.0, 15.0, 1_000_000.0, 6.02e23, 2.5e-3, 10.25].map(Literal::Number)
+        );
+        assert!(scanner.errors.is_empty());
+
+        for bad in ["0x", "1e", "0b102", "1__0", "1_", "12abc", "1e+"] {
+            let mut scanner = Scanner::new(bad.to_string());
+            let tokens = scanner.scan_tokens();
+
+            assert_eq!(scanner.errors.len(), 1, "{}", bad);
+            assert_eq!(tokens.len(), 1, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_arithmetic_operators() {
+        let code = "var a = 7 % 3; var b = -7 % 3; var c = 2 ** 3 ** 2; var d = -2 ** 2; var e = 7 ~/ 2; var f = 0x1F & 0b1010 | 1 << 8 ^ 3; var g = ~5 >> 1; var h = 1 + 2 & 3 == 3;";
+
+        assert_eq!(global(code, false, "a"), "Number(1.0)");
+        assert_eq!(global(code, false, "b"), "Number(-1.0)");
+        assert_eq!(global(code, false, "c"), "Number(512.0)");
+        assert_eq!(global(code, false, "d"), "Number(-4.0)");
+        assert_eq!(global(code, false, "e"), "Number(3.0)");
+        assert_eq!(global(code, false, "f"), "Number(267.0)");
+        assert_eq!(global(code, false, "g"), "Number(-3.0)");
+        assert_eq!(global(code, false, "h"), "True");
+
+        for bad in ["1.5 & 1;", "1 << 64;", "\"a\" % 2;", "~nil;", "1 ~/ 0;"] {
+            let mut i = Interpreter::new(false);
+            assert!(
+                matches!(run(bad, &mut i), Err(Error::Runtime(..))),
+                "{}",
+                bad
+            );
+        }
+
+        let mut i = Interpreter::new(false);
+        match run("\"a\" % 2;", &mut i) {
+            Err(Error::Runtime(_, msg, _)) => assert_eq!(msg, "cannot take modulo of non-numerics"),
+            r => panic!("unexpected {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_compound_assignment() {
+        let code = "var i = 1; i += 4; i *= 3; i -= 1; i /= 2; i **= 2; i %= 10; i <<= 2; var s = \"a\"; s += \"b\"; var a = i++; var b = ++i; var c = i--; var d = --i; for (var j = 0; j < 3; j++) { s += j; }";
+
+        assert_eq!(global(code, false, "i"), "Number(36.0)");
+        assert_eq!(global(code, false, "s"), "StringLit(\"ab012\")");
+        assert_eq!(global(code, false, "a"), "Number(36.0)");
+        assert_eq!(global(code, false, "b"), "Number(38.0)");
+        assert_eq!(global(code, false, "c"), "Number(38.0)");
+        assert_eq!(global(code, false, "d"), "Number(36.0)");
+
+        // only variables can be updated, not list elements
+        for bad in ["xs[0] += 1;", "xs[0]++;", "--xs[0];", "f() += 1;"] {
+            let code = format!("fun f(...xs) {{ {} }}", bad);
+            let mut scanner = Scanner::new(code);
+            scanner.scan_tokens();
+            match scanner.statements().next().unwrap() {
+                Err(Error::Parser(_, msg, _)) => {
+                    assert!(msg.starts_with("Invalid assignment target"))
+                }
+                r => panic!("unexpected {:?}", r.is_ok()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_conditional_and_coalesce() {
+        let code = "var z = 0; var n = nil; var a = 1 > 2 ? \"no\" : 0 ? \"zero\" : \"never\"; var b = n ?? 0 ?? (z = 1); var c = false ?? 1; var d = n?.x.y; var e = true ? 2 : (z = 2); var f = n or 3 ? 4 : 5;";
+
+        assert_eq!(global(code, false, "a"), "StringLit(\"zero\")");
+        assert_eq!(global(code, false, "b"), "Number(0.0)");
+        assert_eq!(global(code, false, "c"), "False");
+        assert_eq!(global(code, false, "d"), "None");
+        assert_eq!(global(code, false, "e"), "Number(2.0)");
+        assert_eq!(global(code, false, "f"), "Number(4.0)");
+        assert_eq!(global(code, false, "z"), "Number(0.0)");
+
+        assert!(fails("var n = nil; n.x;"));
+        assert!(fails("var o = 1; o?.x;"));
+    }
+
+    #[test]
+    fn test_function_parameters() {
+        let code = "fun f(a, b = a * 2, ...rest) { return \"${a} ${b} ${rest} ${len(rest)}\"; }
+            fun many(a1, a2, a3, a4, a5, a6, a7, a8, a9, a10) { return a10; }
+            var x = f(1); var y = f(1, 5, 7, 8); var z = f(b: 3, a: 2); var w = many(1, 2, 3, 4, 5, 6, 7, 8, 9, 10);
+            fun last(...xs) { return xs[len(xs) - 1]; } var v = last(4, 5, 6);";
+
+        assert_eq!(global(code, false, "x"), "StringLit(\"1 2 [] 0\")");
+        assert_eq!(global(code, false, "y"), "StringLit(\"1 5 [7, 8] 2\")");
+        assert_eq!(global(code, false, "z"), "StringLit(\"2 3 [] 0\")");
+        assert_eq!(global(code, false, "w"), "Number(10.0)");
+        assert_eq!(global(code, false, "v"), "Number(6.0)");
+
+        let mut i = Interpreter::new(false);
+        match run("fun g(a, b = 1) { return a; } g(1, 2, 3);", &mut i) {
+            Err(Error::Runtime(_, msg, near)) => {
+                assert_eq!(msg, "expected at most 2 arguments but got 3");
+                assert_eq!(near, "g(a, b?)");
+            }
+            _ => panic!("expected arity error"),
+        }
+        assert!(fails("fun g(a, b = 1) { return a; } g(b: 2);"));
+        assert!(fails("fun g(a) { return a; } g(1, a: 2);"));
+
+        // natives like `len` can be replaced by the script's own declarations
+        assert_eq!(
+            global("var n = len(\"a\"); var len = 3;", false, "len"),
+            "Number(3.0)"
+        );
+        assert_eq!(
+            global("var n = len(\"a\"); var len = 3;", false, "n"),
+            "Number(1.0)"
+        );
+        assert_eq!(
+            global("fun len(x) { return -1; } var n = len(\"a\");", false, "n"),
+            "Number(-1.0)"
+        );
+        assert!(fails("var len = 3; var len = 4;"));
+        assert!(crate::check("var len = 3; print len;").is_empty());
+
+        let mut scanner = Scanner::new("fun g(a = 1, b) { return a; }".to_string());
+        scanner.scan_tokens();
+        assert!(scanner.statements().next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_const() {
+        let code = "const limit = 10; let n = 1; n += limit; { let limit = 2; limit = 3; }";
+        assert_eq!(global(code, false, "n"), "Number(11.0)");
+
+        let mut i = Interpreter::new(false);
+        assert!(matches!(
+            run("const c = 1; fun f() { c = 2; }", &mut i),
+            Err(Error::Resolve(1, _, _))
+        ));
+        assert!(matches!(
+            run("const d = 1; { d++; }", &mut i),
+            Err(Error::Resolve(1, _, _))
+        ));
+
+        // assignments the resolver cannot see are rejected when they run
+        let mut i = Interpreter::new(false);
+        run("fun f() { e = 2; } const e = 1;", &mut i).unwrap();
+        match run("f();", &mut i) {
+            Err(Error::Runtime(1, msg, _)) => assert_eq!(msg, "cannot assign to constant `e`"),
+            r => panic!("unexpected {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_incomplete_input() {
+        use crate::repl::is_incomplete;
+
+        for code in [
+            "fun f(a) {\n  return a;",
+            "print (1 +",
+            "var x = 1",
+            "if (true) { print 1; } else {",
+            "print \"abc",
+            "/* still open",
+            "print \"${1 + 2",
+        ] {
+            assert!(is_incomplete(code), "{:?}", code);
+        }
+
+        for code in ["fun f(a) {\n  return a;\n}", "print 1; { }", "print );", ""] {
+            assert!(!is_incomplete(code), "{:?}", code);
+        }
+
+        let code = "var x; if (false) x = 1; else x = 2; for (var i = 0; i < 3; i++) { x += i; }";
+        assert_eq!(global(code, false, "x"), "Number(5.0)");
+    }
+
+    #[test]
+    fn test_repl_recovery() {
+        let mut repl = Repl::new(false);
+        repl.entry("fun double(x) { return x * 2; }").unwrap();
+        assert!(repl.entry("var a = 1; var b = a.c;").is_err());
+        assert!(repl.entry("print ;").is_err());
+        assert_eq!(
+            repl.command(":env").unwrap(),
+            "double = <fn double>\nlen = <fn len>"
+        );
+
+        // the failed entry can be entered again, and earlier state survives
+        repl.entry("var a = double(2);").unwrap();
+        assert!(repl.command(":env").unwrap().starts_with("a = 4\n"));
+
+        // natives replaced and variables changed by a failed entry come back
+        assert!(repl.entry("var len = 3; a = 5; print nil.x;").is_err());
+        assert_eq!(
+            repl.command(":env").unwrap(),
+            "a = 4\ndouble = <fn double>\nlen = <fn len>"
+        );
+        repl.entry("var n = len(\"ab\"); var len = 1;").unwrap();
+    }
+
+    #[test]
+    fn test_repl_commands() {
+        let mut repl = Repl::new(false);
+        assert_eq!(
+            repl.command(":ast 1 + 2 * -x").unwrap(),
+            "(expr (+ 1 (* 2 (- x))))"
+        );
+        assert_eq!(
+            repl.command(":ast fun f(a, b = 1) { if (a) return b; else a++; }")
+                .unwrap(),
+            "(fun f(a, b?) (block (if (group a) (return b) (expr (post++ a)))))"
+        );
+        assert_eq!(
+            repl.command(":tokens x ?? 1").unwrap(),
+            "IDENTIFIER \"x\"\nQUESTION_QUESTION \"??\"\nNUMBER \"1\"\nEOF \"\""
+        );
+
+        repl.entry("var kept = 1;").unwrap();
+        assert!(repl
+            .command(":time kept += 1")
+            .unwrap()
+            .starts_with("took "));
+        assert!(repl.command(":env").unwrap().contains("kept = 2"));
+
+        repl.command(":reset").unwrap();
+        assert!(!repl.command(":env").unwrap().contains("kept"));
+        assert!(repl
+            .command(":bogus")
+            .unwrap()
+            .starts_with("unknown command"));
+        assert!(repl.command(":quit").is_none());
+    }
+
+    #[test]
+    fn test_line_editor() {
+        use crate::editor::Editor;
+
+        let complete = |word: &str| {
+            ["print", "printer", "var"]
+                .iter()
+                .filter(|w| w.starts_with(word))
+                .map(|w| w.to_string())
+                .collect()
+        };
+        let edit = |history: &[&str], keys: &str| {
+            let mut editor = Editor::new(None);
+            history.iter().for_each(|h| editor.add_history(h));
+            let mut out = Vec::new();
+            editor
+                .edit("> ", &mut keys.as_bytes(), &mut out, &complete)
+                .unwrap()
+        };
+
+        assert_eq!(edit(&[], "pr\t 1;\r").unwrap(), "print 1;");
+        assert_eq!(edit(&[], "v\t\r").unwrap(), "var");
+        assert_eq!(edit(&[], "ab\x1b[Dx\x01y\x05z\r").unwrap(), "yaxbz");
+        assert_eq!(edit(&[], "one two\x17three\x7f\r").unwrap(), "one thre");
+        assert_eq!(
+            edit(&["first", "second"], "\x1b[A\x1b[A\x1b[B!\r").unwrap(),
+            "second!"
+        );
+        assert_eq!(
+            edit(&["var a = 1;", "print a;", "var b;"], "\x12va\x12\r").unwrap(),
+            "var a = 1;"
+        );
+        assert_eq!(edit(&[], "\x04"), None);
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        use crate::{run_file, run_source, Options, EX_DATAERR, EX_IOERR, EX_SOFTWARE};
+
+        let opts = Options {
+            compat: false,
+            args: vec!["a".to_string(), "b".to_string()],
+            ..Options::default()
+        };
+        assert_eq!(
+            run_source("if (len(args) != 2 or args[1] != \"b\") nil.x;", &opts),
+            0
+        );
+        assert_eq!(run_source("print 1 +;", &opts), EX_DATAERR);
+        assert_eq!(run_source("print nil.x;", &opts), EX_SOFTWARE);
+        assert_eq!(run_file("/nonexistent/script.lox", &opts), EX_IOERR);
+    }
+
+    #[test]
+    fn test_check() {
+        use crate::{check, diagnostic};
+
+        let messages = |code: &str| -> Vec<String> {
+            check(code)
+                .iter()
+                .map(|e| match e {
+                    Error::Resolve(line, msg, _) => format!("{}: {}", line, msg),
+                    e => e.to_string(),
+                })
+                .collect()
+        };
+
+        let code = "
+            fun f() { return helper(len(args)) + missing; }
+            fun helper(n) { fun inner() { return later; } var later = n; return inner(); }
+            var x = 1;
+            { var y; var y; }
+            break;
+            while (true) { fun g() { break; } }
+            return;
+            print z;
+            var x = 2;";
+        assert_eq!(
+            messages(code),
+            vec![
+                "5: variable `y` already defined",
+                "6: `break` outside of a loop",
+                "7: `break` outside of a loop",
+                "8: `return` outside of a function",
+                "9: variable `z` is undefined",
+                "10: variable `x` already defined",
+                "2: variable `missing` is undefined",
+            ]
+        );
+        assert!(check("fun f(a, b = a) { while (a) { if (b) break; } return a; }").is_empty());
+
+        // every phase reports, resolving what parsed
+        let code = "var a = 1; #\nvar b = ;\nbreak;\nprint missing;\n";
+        let found: Vec<String> = check(code).iter().map(|e| e.to_string()).collect();
+        assert_eq!(found.len(), 3, "{:?}", found);
+        assert!(matches!(check(code)[0], Error::Lexer(..)));
+        assert!(matches!(check(code)[1], Error::Parser(2, ..)));
+        assert!(matches!(check(code)[2], Error::Resolve(3, ..)));
+
+        let err = Error::Resolve(3, "bad \"x\"".to_string(), "x".to_string());
+        assert_eq!(
+            diagnostic("a.lox", &err).to_string(),
+            r#"{"file":"a.lox","line":3,"kind":"resolve","message":"bad \"x\"","near":"x"}"#
+        );
+    }
+
+    #[test]
+    fn test_format() {
+        use crate::formatter::format;
+
+        let code = "// setup\nvar a=1;var b = -a;  // negate\n\n\n\nfun f(x,y){if(x>y){return x;}else{return y;}}\n/* done */ print f(a:1,y:b)?\"${a+1}\":nil;\nfun g() {}\n";
+        let expected = "\
+// setup
+var a = 1;
+var b = -a; // negate
+
+fun f(x, y) {
+    if (x > y) {
+        return x;
+    } else {
+        return y;
+    }
+}
+/* done */
+print f(a: 1, y: b) ? \"${a + 1}\" : nil;
+fun g() {}
+";
+        assert_eq!(format(code).unwrap(), expected);
+        assert_eq!(format(expected).unwrap(), expected);
+
+        let long =
+            "print someFunction(firstArgument, secondArgument, thirdArgument, fourthArgument, 0x1F);";
+        assert_eq!(
+            format(long).unwrap(),
+            "print someFunction(\n    firstArgument,\n    secondArgument,\n    thirdArgument,\n    fourthArgument,\n    0x1F\n);\n"
+        );
+
+        // unbraced branches share the line of their `if` or go below it
+        let code = "fun f(c, x) {\n    if (c) print x;\n    else print -x;\n}\n";
+        assert_eq!(
+            format(code).unwrap(),
+            "fun f(c, x) {\n    if (c) print x; else print -x;\n}\n"
+        );
+        let long = "if (someLongCondition > anotherLongCondition) print someValue; else if (x) print anotherValue; else print 3;";
+        let expected = "if (someLongCondition > anotherLongCondition)\n    print someValue;\nelse if (x)\n    print anotherValue;\nelse\n    print 3;\n";
+        assert_eq!(format(long).unwrap(), expected);
+        assert_eq!(format(expected).unwrap(), expected);
+
+        assert!(format("print (1;").is_err());
+    }
+
+    #[test]
+    fn test_lint() {
+        use crate::lint::{lint, Config};
+
+        let rules = |code: &str, config: &Config| -> Vec<(u32, &'static str)> {
+            let warnings = lint(code, config).unwrap();
+            warnings.iter().map(|w| (w.line, w.rule)).collect()
+        };
+
+        let code = "\
+fun f(a, b, _c) {
+    var unused = 1;
+    var x = a;
+    {
+        var x = 2;
+        print x;
+    }
+    if (x = 3) return x;
+    return 1;
+    print \"never\";
+}
+fun g(n) {
+    if (n > 1) return n;
+}
+fun h(n) {
+    if (n > 1) return n; else return 0;
+}
+print 1 == \"1\";
+print g(1) == nil;
+for (var i = 0; i < 3; i = i + 1) {
+    break;
+}
+";
+        assert_eq!(
+            rules(code, &Config::default()),
+            vec![
+                (1, "unused-parameter"),
+                (2, "unused-variable"),
+                (5, "shadowing"),
+                (8, "assignment-in-condition"),
+                (10, "unreachable-code"),
+                (12, "missing-return"),
+                (18, "literal-comparison"),
+            ]
+        );
+
+        let config = Config::parse("# quieter\nshadowing = off\nunused-parameter=off\n").unwrap();
+        let allowed = code.replace(
+            "var unused",
+            "// lox-allow(unused-variable)\n    var unused",
+        );
+        assert_eq!(
+            rules(&allowed, &config),
+            vec![
+                (9, "assignment-in-condition"),
+                (11, "unreachable-code"),
+                (13, "missing-return"),
+                (19, "literal-comparison"),
+            ]
+        );
+
+        // parameters are reported on their own line
+        let code = "fun k(\n    used,\n    // lox-allow(unused-parameter)\n    allowed,\n    unused\n) {\n    return used;\n}\n";
+        assert_eq!(
+            rules(code, &Config::default()),
+            vec![(5, "unused-parameter")]
+        );
+
+        assert!(Config::parse("nonsense = off").is_err());
+        assert!(Config::parse("shadowing = maybe").is_err());
+        assert!(lint("print (1;", &Config::default()).is_err());
+    }
+
+    #[test]
+    fn test_json() {
+        use crate::json::Json;
+
+        let text = r#"{"a": [1, -2.5e1, true, null], "b": {"c": "x\"\né😀"}, "d": []}"#;
+        let json = Json::parse(text).unwrap();
+        assert_eq!(
+            json.at(&["b", "c"]).and_then(Json::as_str),
+            Some("x\"\né😀")
+        );
+        assert_eq!(
+            json.get("a").and_then(Json::as_array).map(|a| a.len()),
+            Some(4)
+        );
+        assert_eq!(Json::parse(&json.to_string()).unwrap(), json);
+
+        assert!(Json::parse("{\"a\": }").is_err());
+        assert!(Json::parse("[1, 2").is_err());
+        assert!(Json::parse("1 2").is_err());
+    }
+
+    #[test]
+    fn test_lsp() {
+        use crate::{
+            json::Json,
+            lsp::{read_message, Server},
+        };
+        use std::io::Cursor;
+
+        let code = "/// Adds two numbers.\nfun add(a, b = 1) {\n    return a + b;\n}\nvar total = add(1);\nprint total + add(total, b: 2);\n";
+        let position = |line: u32, character: u32| {
+            format!(
+                r#""textDocument": {{"uri": "file:///a.lox"}}, "position": {{"line": {}, "character": {}}}"#,
+                line, character
+            )
+        };
+        let messages = [
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}"#.to_string(),
+            format!(
+                r#"{{"jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {{"textDocument": {{"uri": "file:///a.lox", "text": {}}}}}}}"#,
+                Json::str(code)
+            ),
+            format!(r#"{{"jsonrpc": "2.0", "id": 2, "method": "textDocument/definition", "params": {{{}}}}}"#, position(5, 16)),
+            format!(
+                r#"{{"jsonrpc": "2.0", "id": 3, "method": "textDocument/references", "params": {{{}, "context": {{"includeDeclaration": false}}}}}}"#,
+                position(1, 11)
+            ),
+            format!(r#"{{"jsonrpc": "2.0", "id": 4, "method": "textDocument/hover", "params": {{{}}}}}"#, position(4, 12)),
+            r#"{"jsonrpc": "2.0", "id": 5, "method": "textDocument/documentSymbol", "params": {"textDocument": {"uri": "file:///a.lox"}}}"#.to_string(),
+            format!(r#"{{"jsonrpc": "2.0", "id": 6, "method": "textDocument/completion", "params": {{{}}}}}"#, position(5, 0)),
+            r#"{"jsonrpc": "2.0", "method": "textDocument/didChange", "params": {"textDocument": {"uri": "file:///a.lox"}, "contentChanges": [{"text": "print missing;\n"}]}}"#.to_string(),
+            r#"{"jsonrpc": "2.0", "id": 7, "method": "nonsense"}"#.to_string(),
+            r#"{"jsonrpc": "2.0", "id": 8, "method": "shutdown"}"#.to_string(),
+            r#"{"jsonrpc": "2.0", "method": "exit"}"#.to_string(),
+        ];
+
+        let mut input = String::new();
+        for msg in &messages {
+            input.push_str(&format!("Content-Length: {}\r\n\r\n{}", msg.len(), msg));
+        }
+        let mut output: Vec<u8> = Vec::new();
+        let status = Server::new()
+            .serve(&mut Cursor::new(input), &mut output)
+            .unwrap();
+        assert_eq!(status, 0);
+
+        let mut replies: Vec<Json> = Vec::new();
+        let mut output = Cursor::new(output);
+        while let Some(body) = read_message(&mut output).unwrap() {
+            replies.push(Json::parse(&body).unwrap());
+        }
+        let result = |id: f64| {
+            replies
+                .iter()
+                .find(|r| r.get("id") == Some(&Json::Number(id)))
+                .and_then(|r| r.get("result"))
+                .map(|r| r.to_string())
+                .unwrap()
+        };
+
+        assert!(result(1.0).contains(r#""definitionProvider":true"#));
+        assert_eq!(
+            replies[1].at(&["params", "diagnostics"]),
+            Some(&Json::Array(vec![]))
+        );
+        assert_eq!(
+            result(2.0),
+            r#"{"uri":"file:///a.lox","range":{"start":{"line":1,"character":4},"end":{"line":1,"character":7}}}"#
+        );
+        assert_eq!(
+            result(3.0),
+            r#"[{"uri":"file:///a.lox","range":{"start":{"line":2,"character":15},"end":{"line":2,"character":16}}}]"#
+        );
+        assert_eq!(
+            result(4.0),
+            r#"{"contents":{"kind":"markdown","value":"```lox\nfun add(a, b?)\n```\n\nAdds two numbers."}}"#
+        );
+        assert!(result(5.0).starts_with(r#"[{"name":"add","detail":"fun add(a, b?)","kind":12"#));
+        assert!(result(5.0).contains(r#"{"name":"total","detail":"var total","kind":13"#));
+        for label in ["\"total\"", "\"while\"", "\"len\""] {
+            assert!(result(6.0).contains(&format!("{{\"label\":{}", label)));
+        }
+
+        let diagnostics = replies
+            .iter()
+            .find_map(|r| {
+                r.at(&["params", "diagnostics"])
+                    .filter(|d| *d != &Json::Array(vec![]))
+            })
+            .unwrap();
+        assert_eq!(
+            diagnostics.to_string(),
+            r#"[{"range":{"start":{"line":0,"character":6},"end":{"line":0,"character":13}},"severity":1,"source":"rlox","message":"variable `missing` is undefined"}]"#
+        );
+        assert!(replies
+            .iter()
+            .any(|r| r.at(&["error", "code"]) == Some(&Json::Number(-32601.0))));
+    }
+
+    #[test]
+    fn test_dap() {
+        use crate::{dap, json::Json, lsp::read_message};
+        use std::{cell::RefCell, io, io::Cursor, rc::Rc};
+
+        struct Shared(Rc<RefCell<Vec<u8>>>);
+        impl io::Write for Shared {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.borrow_mut().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let path = std::env::temp_dir().join("rlox_test_dap.lox");
+        let code = "fun add(a, b) {\n    var sum = a + b;\n    return sum;\n}\nvar x = add(1, 2);\nprint x;\nprint add(x, 10);\n";
+        std::fs::write(&path, code).unwrap();
+        let program = Json::str(path.to_str().unwrap());
+
+        let requests = [
+            ("initialize", "{}".to_string()),
+            ("launch", format!(r#"{{"program": {}}}"#, program)),
+            (
+                "setBreakpoints",
+                format!(
+                    r#"{{"source": {{"path": {}}}, "breakpoints": [{{"line": 3}}]}}"#,
+                    program
+                ),
+            ),
+            ("configurationDone", "{}".to_string()),
+            ("stackTrace", r#"{"threadId": 1}"#.to_string()),
+            ("scopes", r#"{"frameId": 2}"#.to_string()),
+            ("variables", r#"{"variablesReference": 2}"#.to_string()),
+            (
+                "evaluate",
+                r#"{"expression": "sum * 10", "frameId": 2}"#.to_string(),
+            ),
+            ("stepOut", r#"{"threadId": 1}"#.to_string()),
+            ("next", r#"{"threadId": 1}"#.to_string()),
+            ("next", r#"{"threadId": 1}"#.to_string()),
+            // a disconnect would be taken while the program runs, so the
+            // session ends with the input instead
+            ("continue", r#"{"threadId": 1}"#.to_string()),
+        ];
+        let mut input = String::new();
+        for (seq, (command, args)) in requests.iter().enumerate() {
+            let msg = format!(
+                r#"{{"seq": {}, "type": "request", "command": "{}", "arguments": {}}}"#,
+                seq + 1,
+                command,
+                args
+            );
+            input.push_str(&format!("Content-Length: {}\r\n\r\n{}", msg.len(), msg));
+        }
+
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let status = dap::serve(
+            Box::new(Cursor::new(input)),
+            Box::new(Shared(output.clone())),
+        );
+        assert_eq!(status, 0);
+
+        let mut messages: Vec<Json> = Vec::new();
+        let mut output = Cursor::new(output.borrow().clone());
+        while let Some(body) = read_message(&mut output).unwrap() {
+            messages.push(Json::parse(&body).unwrap());
+        }
+        let string = |msg: &Json, path: &[&str]| {
+            msg.at(path)
+                .and_then(Json::as_str)
+                .unwrap_or("")
+                .to_string()
+        };
+
+        // what happened, in order: responses by command, events by name
+        // with the reason for stops and the text of output
+        let log: Vec<String> = messages
+            .iter()
+            .map(|m| match string(m, &["type"]).as_str() {
+                "response" => string(m, &["command"]),
+                _ => match string(m, &["event"]).as_str() {
+                    "stopped" => format!("stopped: {}", string(m, &["body", "reason"])),
+                    "output" => format!("output: {}", string(m, &["body", "output"])),
+                    event => event.to_string(),
+                },
+            })
+            .collect();
+        assert_eq!(
+            log,
+            [
+                "initialize",
+                "initialized",
+                "launch",
+                "setBreakpoints",
+                "configurationDone",
+                "stopped: breakpoint",
+                "stackTrace",
+                "scopes",
+                "variables",
+                "evaluate",
+                "stepOut",
+                "stopped: step",
+                "next",
+                "output: 3\n",
+                "stopped: step",
+                "next",
+                "stopped: breakpoint",
+                "continue",
+                "output: 13\n",
+                "exited",
+                "terminated",
+            ]
+        );
+
+        let body = |command: &str| {
+            messages
+                .iter()
+                .find(|m| m.get("command").and_then(Json::as_str) == Some(command))
+                .and_then(|m| m.get("body"))
+                .unwrap()
+                .to_string()
+        };
+        let frames = body("stackTrace");
+        assert!(frames.contains(r#""id":2,"name":"add","#));
+        assert!(frames.contains(r#""line":3,"#));
+        assert!(frames.contains(r#""id":1,"name":"<script>","#));
+        assert!(frames.contains(r#""line":5,"#));
+        assert!(body("scopes").starts_with(r#"{"scopes":[{"name":"Locals","variablesReference":1"#));
+        assert_eq!(
+            body("variables"),
+            r#"{"variables":[{"name":"a","value":"1","variablesReference":0},{"name":"b","value":"2","variablesReference":0}]}"#
+        );
+        assert_eq!(
+            body("evaluate"),
+            r#"{"result":"30","variablesReference":0}"#
+        );
+    }
+
+    #[test]
+    fn test_debugger() {
+        use crate::{debugger, Options};
+        use std::{cell::RefCell, io, io::Cursor, rc::Rc};
+
+        struct Shared(Rc<RefCell<Vec<u8>>>);
+        impl io::Write for Shared {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.borrow_mut().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let path = std::env::temp_dir().join("rlox_test_debugger.lox");
+        let code = "fun add(a, b) {\n    var sum = a + b;\n    return sum;\n}\nvar x = add(1, 2);\nprint x;\nx = add(x, 10);\nprint x;\n";
+        std::fs::write(&path, code).unwrap();
+
+        let commands =
+            "next\nb 3\nrun\nbt\nlocals\np sum * 10\nfinish\nwatch x\nc\nnext\nc\nquit\n";
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let status = debugger::debug(
+            path.to_str().unwrap(),
+            &Options::default(),
+            Box::new(Cursor::new(commands)),
+            Box::new(Shared(output.clone())),
+        );
+        assert_eq!(status, 0);
+
+        let output = String::from_utf8(output.borrow().clone()).unwrap();
+        let replies: Vec<&str> = output.split("(rlox) ").skip(1).collect();
+        assert_eq!(
+            replies,
+            [
+                "the program is not running, type `run` to start it\n",
+                "breakpoint at line 3\n",
+                "breakpoint at line 3\n3\t    return sum;\n",
+                "#0 add at line 3\n#1 <script> at line 5\n",
+                "sum = 3\na = 1\nb = 2\n",
+                "30\n",
+                "6\tprint x;\n",
+                "watching x\n",
+                "3\nbreakpoint at line 3\n3\t    return sum;\n",
+                "x changed from 3 to 13\n8\tprint x;\n",
+                "13\nprogram exited with status 0\n",
+                "",
+            ]
+        );
+
+        // a loop on one line stops at its breakpoint on every iteration
+        std::fs::write(&path, "var i = 0;\nwhile (i < 3) i++;\nprint i;\n").unwrap();
+        let output = Rc::new(RefCell::new(Vec::new()));
+        debugger::debug(
+            path.to_str().unwrap(),
+            &Options::default(),
+            Box::new(Cursor::new("b 2\nrun\nc\nc\nc\n")),
+            Box::new(Shared(output.clone())),
+        );
+        let output = String::from_utf8(output.borrow().clone()).unwrap();
+        assert_eq!(output.matches("breakpoint at line 2\n2\t").count(), 3);
+        assert!(output.contains("3\nprogram exited with status 0"));
+    }
+
+    #[test]
+    fn test_profile() {
+        use crate::profile::Profiler;
+        use std::{cell::RefCell, rc::Rc};
+
+        let code = "fun fib(n) {\n    if (n < 2) return n;\n    return fib(n - 1) + fib(n - 2);\n}\nfun main() {\n    print fib(5);\n}\nmain();\nmain();\n";
+        let profiler = Rc::new(RefCell::new(Profiler::new()));
+        profiler.borrow_mut().start();
+        let mut i = Interpreter::new(false).hooks(profiler.clone());
+        run(code, &mut i).unwrap();
+
+        let mut profiler = profiler.borrow_mut();
+        profiler.finish();
+
+        let calls = |name: &str| {
+            profiler
+                .functions()
+                .iter()
+                .find(|f| f.name == name)
+                .map(|f| f.calls)
+        };
+        assert_eq!(calls("<script>"), Some(1));
+        assert_eq!(calls("main"), Some(2));
+        assert_eq!(calls("fib"), Some(30));
+
+        // recursion counts the time of the outermost call only
+        let fib = profiler
+            .functions()
+            .into_iter()
+            .find(|f| f.name == "fib")
+            .unwrap();
+        let main = profiler
+            .functions()
+            .into_iter()
+            .find(|f| f.name == "main")
+            .unwrap();
+        assert!(fib.total <= main.total);
+        assert!(fib.own <= fib.total);
+
+        let hits: Vec<(u32, u64)> = {
+            let mut hits: Vec<(u32, u64)> =
+                profiler.lines().iter().map(|l| (l.line, l.hits)).collect();
+            hits.sort();
+            hits
+        };
+        // an `if` and its `return` are two statements on line 2
+        assert_eq!(
+            hits,
+            [(1, 1), (2, 46), (3, 14), (5, 1), (6, 2), (8, 1), (9, 1)]
+        );
+
+        let folded = profiler.folded();
+        let stacks: Vec<&str> = folded
+            .lines()
+            .map(|l| l.rsplit_once(' ').unwrap().0)
+            .collect();
+        assert_eq!(
+            stacks[..3],
+            ["<script>", "<script>;main", "<script>;main;fib"]
+        );
+        assert_eq!(stacks.len(), 7);
+
+        let report = profiler.report(code);
+        assert!(report.contains("      30"));
+        assert!(report.contains("  3     return fib(n - 1) + fib(n - 2);"));
+    }
+
+    #[test]
+    fn test_coverage() {
+        use crate::{analyze, coverage::Coverage, resolver::Resolver};
+        use std::{cell::RefCell, rc::Rc};
+
+        let code = "fun sign(n) {\n    if (n < 0) return -1;\n    return n == 0 ? 0 : 1;\n}\nfun unused() {\n    print \"never\";\n}\nvar i = 0;\nwhile (i < 3) {\n    sign(i);\n    i++;\n}\nvar x = nil or 5;\n";
+        let stmts = analyze(code, Resolver::new()).unwrap();
+        let coverage = Rc::new(RefCell::new(Coverage::new(&stmts)));
+        let mut i = Interpreter::new(false).hooks(coverage.clone());
+        for stmt in &stmts {
+            i.interpret(stmt).unwrap();
+        }
+
+        let coverage = coverage.borrow();
+        assert_eq!(
+            coverage.lcov("a.lox"),
+            "TN:\nSF:a.lox\n\
+             FN:1,sign\nFN:5,unused\nFNDA:3,sign\nFNDA:0,unused\nFNF:2\nFNH:1\n\
+             BRDA:2,0,0,0\nBRDA:2,0,1,3\nBRDA:3,1,0,1\nBRDA:3,1,1,2\n\
+             BRDA:9,2,0,3\nBRDA:9,2,1,1\nBRDA:13,3,0,0\nBRDA:13,3,1,1\nBRF:8\nBRH:6\n\
+             DA:1,1\nDA:2,3\nDA:3,3\nDA:5,1\nDA:6,0\nDA:8,1\nDA:9,1\nDA:10,3\nDA:11,3\nDA:13,1\n\
+             LF:10\nLH:9\nend_of_record\n"
+        );
+        assert_eq!(
+            coverage.summary("a.lox"),
+            "coverage of a.lox\n\
+             \x20 lines          9/10    90.0%\n\
+             \x20 branches       6/8     75.0%\n\
+             \x20 functions      1/2     50.0%\n\
+             \x20 lines not run: 6\n\
+             \x20 condition at line 2 never true\n\
+             \x20 condition at line 13 never true\n\
+             \x20 function unused at line 5 never called\n"
+        );
+
+        // functions with the same name are told apart
+        let code = "fun a() {\n    fun helper() {}\n    helper();\n}\n\
+                    fun b() {\n    fun helper() {}\n}\na();\nb();\n";
+        let stmts = analyze(code, Resolver::new()).unwrap();
+        let coverage = Rc::new(RefCell::new(Coverage::new(&stmts)));
+        let mut i = Interpreter::new(false).hooks(coverage.clone());
+        for stmt in &stmts {
+            i.interpret(stmt).unwrap();
+        }
+
+        let lcov = coverage.borrow().lcov("b.lox");
+        assert!(lcov.contains("FNDA:1,a\nFNDA:1,helper\nFNDA:1,b\nFNDA:0,helper\nFNF:4\nFNH:3\n"));
+    }
+
+    #[test]
+    fn test_trace() {
+        use crate::trace::Tracer;
+        use std::{cell::RefCell, io, rc::Rc};
+
+        struct Shared(Rc<RefCell<Vec<u8>>>);
+        impl io::Write for Shared {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.borrow_mut().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let trace = |code: &str, functions: &[&str]| {
+            let output = Rc::new(RefCell::new(Vec::new()));
+            let functions = functions.iter().map(|f| f.to_string()).collect();
+            let tracer = Tracer::new(code, Box::new(Shared(output.clone())), functions);
+            let mut i = Interpreter::new(false).hooks(Rc::new(RefCell::new(tracer)));
+            let _ = run(code, &mut i);
+            let output = output.borrow().clone();
+            String::from_utf8(output).unwrap()
+        };
+
+        let code = "fun twice(s) {\n    var t = s;\n    t += s;\n    return t;\n}\nfun count(n) {\n    if (n > 0) count(n - 1);\n}\nvar x = twice(\"ab\");\nx = count(1);\n";
+        assert_eq!(
+            trace(code, &[]),
+            "1: fun twice(s) {\n\
+             6: fun count(n) {\n\
+             9: var x = twice(\"ab\");\n\
+             call twice(s: \"ab\")\n\
+             \x20 2: var t = s;\n\
+             \x20 3: t += s;\n\
+             \x20 t = \"abab\" (was \"ab\")\n\
+             \x20 4: return t;\n\
+             twice returned \"abab\"\n\
+             10: x = count(1);\n\
+             call count(n: 1)\n\
+             \x20 7: if (n > 0) count(n - 1);\n\
+             \x20 7: if (n > 0) count(n - 1);\n\
+             \x20 call count(n: 0)\n\
+             \x20   7: if (n > 0) count(n - 1);\n\
+             \x20 count returned nil\n\
+             count returned nil\n\
+             x = nil (was \"abab\")\n"
+        );
+
+        // only inside calls to the named functions, here a recursive one
+        assert_eq!(
+            trace(code, &["count"]),
+            "call count(n: 1)\n\
+             \x20 7: if (n > 0) count(n - 1);\n\
+             \x20 7: if (n > 0) count(n - 1);\n\
+             \x20 call count(n: 0)\n\
+             \x20   7: if (n > 0) count(n - 1);\n\
+             \x20 count returned nil\n\
+             count returned nil\n"
+        );
+    }
+
+    #[test]
+    fn test_testing() {
+        use crate::{testing, Options};
+
+        let dir = std::env::temp_dir().join("rlox_test_testing");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(
+            dir.join("math_test.lox"),
+            "var calls = 0;\nfun add(a, b) { calls++; return a + b; }\n\
+             fun test_add() {\n    assert_eq(add(1, 2), 3);\n    assert_eq(calls, 1);\n}\n\
+             fun test_wrong() {\n    print \"adding\";\n    assert_eq(add(2, 2), 5);\n}\n\
+             fun test_throws() {\n    fun bad() { return nil.x; }\n    assert_eq(assert_throws(bad), \"undefined property `x`\");\n}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("sub/str_test.lox"),
+            "fun test_concat() { assert(\"a\" + \"b\" == \"ab\"); }\n",
+        )
+        .unwrap();
+        // not a test file, so only run when named
+        std::fs::write(
+            dir.join("helper.lox"),
+            "fun test_helper() { assert(nil); }\n",
+        )
+        .unwrap();
+
+        let run = |paths: &[&str]| {
+            let paths: Vec<String> = paths
+                .iter()
+                .map(|p| dir.join(p).to_string_lossy().to_string())
+                .collect();
+            let mut out = Vec::new();
+            let status = testing::run(&paths, &Options::default(), &mut out);
+            // without the timings, which vary
+            let out = String::from_utf8(out).unwrap();
+            let out: Vec<&str> = out
+                .lines()
+                .map(|l| l.split(" (").next().unwrap())
+                .map(|l| l.split("; finished").next().unwrap())
+                .collect();
+            (
+                status,
+                out.join("\n")
+                    .replace(&dir.to_string_lossy().to_string(), "dir"),
+            )
+        };
+
+        let (status, out) = run(&[""]);
+        assert_eq!(status, 1);
+        assert_eq!(
+            out,
+            "running 3 tests in dir/math_test.lox\n\
+             test test_add ... ok\n\
+             test test_wrong ... FAILED\n\
+             test test_throws ... ok\n\
+             running 1 test in dir/sub/str_test.lox\n\
+             test test_concat ... ok\n\
+             \n\
+             failures:\n\
+             \n\
+             ---- dir/math_test.lox test_wrong ----\n\
+             Runtime Error at [line: 9] expected 5 but got 4 : near assert_eq\n\
+             \x20  9 | assert_eq(add(2, 2), 5);\n\
+             printed:\n\
+             adding\n\
+             \n\
+             test result: FAILED. 3 passed; 1 failed"
+        );
+
+        let (status, out) = run(&["helper.lox"]);
+        assert_eq!(status, 1);
+        assert!(out.contains("Runtime Error at [line: 1] assertion failed, got nil : near assert"));
+        let (status, out) = run(&["sub"]);
+        assert_eq!(status, 0);
+        assert!(out.ends_with("test result: ok. 1 passed; 0 failed"));
+
+        // the assertions only exist in tests, so scripts can declare their own
+        let mut i = Interpreter::new(false);
+        assert!(i.env.get("assert").is_err());
+        crate::run("fun assert(c) { return c; } var ok = assert(true);", &mut i).unwrap();
+    }
 }
@@ -0,0 +1,478 @@
+use std::{collections::HashMap, fmt, ops::Deref, rc::Rc};
+
+use crate::{
+    analyze,
+    error::Error,
+    expr::{Expr, Visitor as ExprVisitor},
+    function::Signature,
+    resolver::Resolver,
+    scanner::Scanner,
+    stmt::{Stmt, Visitor as StmtVisitor},
+    token::{Token, TokenType::*},
+};
+
+/// Every rule with what it warns about.
+pub const RULES: &[(&str, &str)] = &[
+    (
+        "unused-variable",
+        "local variables and functions that are never read",
+    ),
+    ("unused-parameter", "parameters that are never read"),
+    (
+        "shadowing",
+        "declarations that hide a variable of an enclosing scope",
+    ),
+    ("unreachable-code", "statements after a `return` or `break`"),
+    (
+        "assignment-in-condition",
+        "`=` used as the condition of `if` or a loop",
+    ),
+    (
+        "literal-comparison",
+        "comparisons between values of obviously different types",
+    ),
+    (
+        "missing-return",
+        "functions that return a value on some paths but not all",
+    ),
+];
+
+/// The name of the file `rlox lint` reads its settings from.
+pub const CONFIG_FILE: &str = ".loxlint";
+
+/// Which rules are on. Every rule is on unless the config turns it off.
+#[derive(Default)]
+pub struct Config {
+    disabled: Vec<String>,
+}
+
+impl Config {
+    /// Reads `rule = on|off` lines; `#` starts a comment.
+    pub fn parse(text: &str) -> Result<Config, String> {
+        let mut config = Config::default();
+
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (rule, setting) = match line.split_once('=') {
+                Some((rule, setting)) => (rule.trim(), setting.trim()),
+                None => return Err(format!("line {}: expected `rule = on|off`", n + 1)),
+            };
+            if !RULES.iter().any(|(name, _)| *name == rule) {
+                return Err(format!("line {}: unknown rule `{}`", n + 1, rule));
+            }
+
+            config.disabled.retain(|r| r != rule);
+            match setting {
+                "on" => {}
+                "off" => config.disabled.push(rule.to_string()),
+                _ => return Err(format!("line {}: expected `on` or `off`", n + 1)),
+            }
+        }
+
+        Ok(config)
+    }
+
+    pub fn enabled(&self, rule: &str) -> bool {
+        !self.disabled.iter().any(|r| r == rule)
+    }
+}
+
+#[derive(Debug)]
+pub struct Warning {
+    pub line: u32,
+    pub rule: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Warning at [line: {}] {} [{}]",
+            self.line, self.message, self.rule
+        )
+    }
+}
+
+/// Finds the suspicious but valid code in a program, in line order. Code
+/// that does not compile is reported as errors instead.
+pub fn lint(code: &str, config: &Config) -> Result<Vec<Warning>, Vec<Error>> {
+    let stmts = analyze(code, Resolver::new())?;
+
+    let mut linter = Linter {
+        scopes: vec![HashMap::new()],
+        warnings: Vec::new(),
+    };
+    for stmt in &stmts {
+        stmt.accept(&mut linter);
+    }
+    linter.unreachable(&stmts);
+
+    let allowed = allowances(code);
+    let mut warnings: Vec<Warning> = linter
+        .warnings
+        .into_iter()
+        .filter(|w| config.enabled(w.rule))
+        .filter(|w| {
+            !allowed
+                .iter()
+                .any(|(line, rule)| *rule == w.rule && (*line == w.line || *line + 1 == w.line))
+        })
+        .collect();
+    warnings.sort_by_key(|w| w.line);
+    Ok(warnings)
+}
+
+/// The rules named by `// lox-allow(rule, ...)` comments, with the line of
+/// the comment. An allowance covers its own line and the one below it.
+fn allowances(code: &str) -> Vec<(u32, String)> {
+    let mut allowed = Vec::new();
+
+    for token in Scanner::new(code.to_string()).lossless(true).scan_tokens() {
+        if token.token_type != COMMENT {
+            continue;
+        }
+
+        let text = token.lexeme.trim_start_matches('/').trim();
+        let rules = text
+            .strip_prefix("lox-allow(")
+            .and_then(|rest| rest.split_once(')'))
+            .map(|(rules, _)| rules);
+        if let Some(rules) = rules {
+            for rule in rules.split(',') {
+                allowed.push((token.line, rule.trim().to_string()));
+            }
+        }
+    }
+    allowed
+}
+
+struct Local {
+    line: u32,
+    used: bool,
+    parameter: bool,
+}
+
+/// Walks the program with a scope per block, like the resolver, recording
+/// where names are declared and whether anything reads them.
+struct Linter {
+    scopes: Vec<HashMap<String, Local>>,
+    warnings: Vec<Warning>,
+}
+
+impl Linter {
+    fn warn(&mut self, line: u32, rule: &'static str, message: String) {
+        self.warnings.push(Warning {
+            line,
+            rule,
+            message,
+        });
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        let scope = match self.scopes.pop() {
+            Some(scope) => scope,
+            None => return,
+        };
+
+        // a leading underscore marks a name as unused on purpose
+        for (name, local) in scope {
+            if local.used || name.starts_with('_') {
+                continue;
+            }
+            match local.parameter {
+                true => self.warn(
+                    local.line,
+                    "unused-parameter",
+                    format!("parameter `{}` is never used", name),
+                ),
+                false => self.warn(
+                    local.line,
+                    "unused-variable",
+                    format!("`{}` is never used", name),
+                ),
+            }
+        }
+    }
+
+    fn declare(&mut self, name: &str, line: u32, parameter: bool) {
+        let outer = self.scopes.len() - 1;
+        if self.scopes[..outer].iter().any(|s| s.contains_key(name)) {
+            self.warn(
+                line,
+                "shadowing",
+                format!("`{}` shadows a variable of an enclosing scope", name),
+            );
+        }
+
+        // globals may be read by code loaded later, so only locals count
+        let used = outer == 0;
+        self.scopes.last_mut().unwrap().insert(
+            name.to_string(),
+            Local {
+                line,
+                used,
+                parameter,
+            },
+        );
+    }
+
+    fn read(&mut self, name: &Token) {
+        let scope = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find_map(|s| s.get_mut(&name.lexeme));
+        if let Some(local) = scope {
+            local.used = true;
+        }
+    }
+
+    fn condition(&mut self, cond: &Expr) {
+        let mut inner = cond;
+        while let Expr::Grouping(e) = inner {
+            inner = e;
+        }
+
+        if let Expr::Assignment(..) | Expr::Compound(..) = inner {
+            self.warn(
+                cond.line(),
+                "assignment-in-condition",
+                "assignment used as a condition, did you mean `==`?".to_string(),
+            );
+        }
+        cond.accept(self);
+    }
+
+    /// Warns about the first statement that follows a `return` or `break`.
+    fn unreachable(&mut self, stmts: &[Stmt]) {
+        // a nested block is not enough: `for` loops put their increment in
+        // a block after the body, which may end in `return` or `break`
+        let jump = stmts
+            .iter()
+            .position(|s| !matches!(s, Stmt::Block(_)) && jumps(s));
+
+        let line = jump.and_then(|i| stmts[i + 1..].iter().find_map(|s| s.line()));
+        if let Some(line) = line {
+            self.warn(line, "unreachable-code", "unreachable code".to_string());
+        }
+    }
+}
+
+/// Whether control never continues past `s`.
+fn jumps(s: &Stmt) -> bool {
+    match s {
+        Stmt::Return(..) | Stmt::Break(_) => true,
+        Stmt::Block(ss) => ss.iter().any(jumps),
+        Stmt::If(_, t, Some(e)) => jumps(t) && jumps(e),
+        Stmt::Doc(_, s) => jumps(s),
+        _ => false,
+    }
+}
+
+/// Whether every path through `s` ends in a `return`.
+fn returns(s: &Stmt) -> bool {
+    match s {
+        Stmt::Return(..) => true,
+        Stmt::Block(ss) => ss.iter().any(returns),
+        Stmt::If(_, t, Some(e)) => returns(t) && returns(e),
+        Stmt::While(Expr::Literal(t), body) => t.token_type == TRUE && !breaks(body),
+        Stmt::Doc(_, s) => returns(s),
+        _ => false,
+    }
+}
+
+/// Whether `s` has a `return` with a value other than `nil`, outside of
+/// nested functions.
+fn returns_value(s: &Stmt) -> bool {
+    match s {
+        Stmt::Return(_, Expr::Literal(t)) => t.token_type != NIL,
+        Stmt::Return(..) => true,
+        Stmt::Block(ss) => ss.iter().any(returns_value),
+        Stmt::If(_, t, e) => returns_value(t) || e.as_deref().map_or(false, returns_value),
+        Stmt::While(_, body) => returns_value(body),
+        Stmt::Doc(_, s) => returns_value(s),
+        _ => false,
+    }
+}
+
+/// Whether `s` has a `break` that leaves the loop it is the body of.
+fn breaks(s: &Stmt) -> bool {
+    match s {
+        Stmt::Break(_) => true,
+        Stmt::Block(ss) => ss.iter().any(breaks),
+        Stmt::If(_, t, e) => breaks(t) || e.as_deref().map_or(false, breaks),
+        Stmt::Doc(_, s) => breaks(s),
+        _ => false,
+    }
+}
+
+/// The type of an expression whose value is obvious without running it.
+fn obvious_type(e: &Expr) -> Option<&'static str> {
+    match e {
+        Expr::Literal(t) => match t.token_type {
+            NUMBER => Some("number"),
+            STRING => Some("string"),
+            TRUE | FALSE => Some("boolean"),
+            NIL => Some("nil"),
+            _ => None,
+        },
+        Expr::Interpolation(_) => Some("string"),
+        Expr::Unary(op, _) if op.token_type == BANG => Some("boolean"),
+        Expr::Binary(_, op, _) if comparison(op) => Some("boolean"),
+        Expr::Grouping(e) => obvious_type(e),
+        _ => None,
+    }
+}
+
+fn comparison(op: &Token) -> bool {
+    matches!(
+        op.token_type,
+        EQUAL_EQUAL | BANG_EQUAL | LESS | LESS_EQUAL | GREATER | GREATER_EQUAL
+    )
+}
+
+impl ExprVisitor<()> for Linter {
+    fn visit_expr(&mut self, expr: &Expr) {
+        match *expr {
+            Expr::Identifier(ref name) => self.read(name),
+            Expr::Literal(_) => {}
+            Expr::Grouping(ref inside) => inside.accept(self),
+            Expr::Unary(_, ref rhs) => rhs.accept(self),
+            Expr::Binary(ref lhs, ref op, ref rhs) => {
+                if let (Some(l), Some(r)) = (obvious_type(lhs), obvious_type(rhs)) {
+                    if comparison(op) && l != r {
+                        self.warn(
+                            op.line,
+                            "literal-comparison",
+                            format!("comparing a {} with a {}", l, r),
+                        );
+                    }
+                }
+                lhs.accept(self);
+                rhs.accept(self);
+            }
+            // writing to a variable is not a use of it
+            Expr::Assignment(_, ref rhs) | Expr::Compound(_, _, ref rhs) => rhs.accept(self),
+            Expr::Update(..) => {}
+            Expr::Call(ref callee, _, ref args, ref named) => {
+                callee.accept(self);
+                args.iter().for_each(|a| a.accept(self));
+                named.iter().for_each(|(_, a)| a.accept(self));
+            }
+            Expr::Index(ref object, _, ref index) => {
+                object.accept(self);
+                index.accept(self);
+            }
+            Expr::Interpolation(ref parts) => parts.iter().for_each(|p| p.accept(self)),
+            Expr::Conditional(ref c, ref t, ref e) => {
+                c.accept(self);
+                t.accept(self);
+                e.accept(self);
+            }
+            Expr::Get(ref object, _, _) => object.accept(self),
+        }
+    }
+}
+
+impl StmtVisitor<()> for Linter {
+    fn visit_stmt(&mut self, s: &Stmt) {
+        match *s {
+            Stmt::Empty => {}
+            Stmt::Break(l) => self.visit_break(l),
+            Stmt::Print(ref e) => self.visit_print_stmt(e),
+            Stmt::Expression(ref e) => self.visit_expression_stmt(e),
+            Stmt::Block(ref ss) => self.visit_block_stmt(ss),
+            Stmt::Declaration(ref n, ref e) => self.visit_declaration_stmt(n, e.as_ref()),
+            Stmt::Const(ref n, ref e) => self.visit_const_stmt(n, e),
+            Stmt::If(ref c, ref t, ref e) => {
+                self.visit_if(c, t.as_ref(), e.as_ref().map(|x| x.deref()))
+            }
+            Stmt::While(ref e, ref b) => self.visit_while(e, b.deref()),
+            Stmt::Return(l, ref e) => self.visit_return(l, e),
+            Stmt::Function(ref n, ref s, ref b) => self.visit_function(n, s.clone(), b.clone()),
+            Stmt::Doc(_, ref s) => self.visit_stmt(s),
+        }
+    }
+
+    fn visit_block_stmt(&mut self, statements: &Vec<Stmt>) {
+        self.unreachable(statements);
+        self.begin_scope();
+        for stmt in statements {
+            stmt.accept(self);
+        }
+        self.end_scope();
+    }
+
+    fn visit_expression_stmt(&mut self, expression: &Expr) {
+        expression.accept(self)
+    }
+
+    fn visit_print_stmt(&mut self, expression: &Expr) {
+        expression.accept(self)
+    }
+
+    fn visit_declaration_stmt(&mut self, name: &Token, init: Option<&Expr>) {
+        if let Some(init) = init {
+            init.accept(self);
+        }
+        self.declare(&name.lexeme, name.line, false);
+    }
+
+    fn visit_const_stmt(&mut self, name: &Token, init: &Expr) {
+        init.accept(self);
+        self.declare(&name.lexeme, name.line, false);
+    }
+
+    fn visit_if(&mut self, expr: &Expr, then_stmt: &Stmt, else_stmt: Option<&Stmt>) {
+        self.condition(expr);
+        then_stmt.accept(self);
+        if let Some(else_stmt) = else_stmt {
+            else_stmt.accept(self);
+        }
+    }
+
+    fn visit_while(&mut self, expr: &Expr, body: &Stmt) {
+        self.condition(expr);
+        body.accept(self);
+    }
+
+    fn visit_break(&mut self, _line: u32) {}
+
+    fn visit_function(&mut self, name: &Token, signature: Rc<Signature>, body: Rc<Stmt>) {
+        self.declare(&name.lexeme, name.line, false);
+
+        if returns_value(&body) && !returns(&body) {
+            self.warn(
+                name.line,
+                "missing-return",
+                format!(
+                    "`{}` returns a value on some paths but can reach its end",
+                    name.lexeme
+                ),
+            );
+        }
+
+        self.begin_scope();
+        for param in &signature.params {
+            if let Some(ref default) = param.default {
+                default.accept(self);
+            }
+            self.declare(&param.name, param.line, true);
+        }
+        body.accept(self);
+        self.end_scope();
+    }
+
+    fn visit_return(&mut self, _line: u64, expr: &Expr) {
+        expr.accept(self)
+    }
+}
@@ -0,0 +1,419 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+};
+
+use crate::{
+    check,
+    error::Error,
+    interpreter::Interpreter,
+    json::Json,
+    scanner::{self, Scanner, StmtIterator},
+    symbols::{Index, Kind, Occurrence},
+};
+
+const PARSE_ERROR: f64 = -32700.0;
+const INVALID_REQUEST: f64 = -32600.0;
+const METHOD_NOT_FOUND: f64 = -32601.0;
+const INVALID_PARAMS: f64 = -32602.0;
+
+/// Reads one message framed by a `Content-Length` header, as used by both
+/// the language server and debug adapter protocols. `None` at end of input.
+pub(crate) fn read_message(input: &mut dyn BufRead) -> io::Result<Option<String>> {
+    let mut length: Option<usize> = None;
+
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+
+        let header = header.trim_end();
+        if header.is_empty() {
+            if length.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().ok();
+            }
+        }
+    }
+
+    let mut body = vec![0u8; length.unwrap_or(0)];
+    input.read_exact(&mut body)?;
+    Ok(Some(String::from_utf8_lossy(&body).into_owned()))
+}
+
+pub(crate) fn write_message(output: &mut dyn Write, msg: &Json) -> io::Result<()> {
+    let body = msg.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+fn position(line: u32, column: u32) -> Json {
+    // the protocol counts lines from zero
+    Json::object(vec![
+        ("line", Json::Number(line.saturating_sub(1) as f64)),
+        ("character", Json::Number(column as f64)),
+    ])
+}
+
+fn range(line: u32, column: u32, len: u32) -> Json {
+    Json::object(vec![
+        ("start", position(line, column)),
+        ("end", position(line, column + len)),
+    ])
+}
+
+fn location(uri: &str, o: &Occurrence) -> Json {
+    Json::object(vec![
+        ("uri", Json::str(uri)),
+        ("range", range(o.line, o.column, o.len)),
+    ])
+}
+
+/// A language server for Lox over the Language Server Protocol: diagnostics,
+/// go to definition, references, hover, document symbols and completion.
+/// Documents are synced in full on every change.
+pub struct Server {
+    documents: HashMap<String, String>,
+    shutdown: bool,
+}
+
+impl Server {
+    pub fn new() -> Self {
+        Server {
+            documents: HashMap::new(),
+            shutdown: false,
+        }
+    }
+
+    /// Answers messages until the client sends `exit`, returning the exit
+    /// status the protocol asks for.
+    pub fn serve(&mut self, input: &mut dyn BufRead, output: &mut dyn Write) -> io::Result<i32> {
+        while let Some(body) = read_message(input)? {
+            let replies = match Json::parse(&body) {
+                Ok(msg) if msg.get("method").and_then(Json::as_str) == Some("exit") => {
+                    return Ok(if self.shutdown { 0 } else { 1 });
+                }
+                Ok(msg) => self.handle(&msg),
+                Err(e) => vec![error(Json::Null, PARSE_ERROR, e)],
+            };
+
+            for reply in &replies {
+                write_message(output, reply)?;
+            }
+        }
+        Ok(1)
+    }
+
+    /// Handles one message, returning the response to a request followed by
+    /// any notifications for the client.
+    pub fn handle(&mut self, msg: &Json) -> Vec<Json> {
+        let method = msg.get("method").and_then(Json::as_str).unwrap_or("");
+        let params = msg.get("params").unwrap_or(&Json::Null);
+
+        // responses to requests we never send are dropped along with
+        // notifications we do not know
+        let id = match msg.get("id") {
+            Some(id) if !method.is_empty() => id.clone(),
+            _ => return self.notification(method, params),
+        };
+
+        if self.shutdown {
+            return vec![error(
+                id,
+                INVALID_REQUEST,
+                "the server is shutting down".to_string(),
+            )];
+        }
+
+        let result = match method {
+            "initialize" => Some(capabilities()),
+            "shutdown" => {
+                self.shutdown = true;
+                Some(Json::Null)
+            }
+            "textDocument/definition" => self.definition(params),
+            "textDocument/references" => self.references(params),
+            "textDocument/hover" => self.hover(params),
+            "textDocument/documentSymbol" => self.symbols(params),
+            "textDocument/completion" => Some(self.completion(params)),
+            _ => {
+                return vec![error(
+                    id,
+                    METHOD_NOT_FOUND,
+                    format!("unknown method `{}`", method),
+                )]
+            }
+        };
+
+        match result {
+            Some(result) => vec![Json::object(vec![
+                ("jsonrpc", Json::str("2.0")),
+                ("id", id),
+                ("result", result),
+            ])],
+            None => vec![error(
+                id,
+                INVALID_PARAMS,
+                "missing document or position".to_string(),
+            )],
+        }
+    }
+
+    fn notification(&mut self, method: &str, params: &Json) -> Vec<Json> {
+        let uri = match params.at(&["textDocument", "uri"]).and_then(Json::as_str) {
+            Some(uri) => uri.to_string(),
+            None => return Vec::new(),
+        };
+
+        let text = match method {
+            "textDocument/didOpen" => params.at(&["textDocument", "text"]),
+            // full sync, so the last change holds the whole text
+            "textDocument/didChange" => params
+                .get("contentChanges")
+                .and_then(Json::as_array)
+                .and_then(|changes| changes.last())
+                .and_then(|change| change.get("text")),
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                return vec![publish(&uri, Vec::new())];
+            }
+            _ => return Vec::new(),
+        };
+
+        match text.and_then(Json::as_str) {
+            Some(text) => {
+                self.documents.insert(uri.clone(), text.to_string());
+                vec![publish(&uri, diagnostics(text))]
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// The document a request is about, indexed, with the symbol under the
+    /// requested position if any.
+    fn lookup(&self, params: &Json) -> Option<(String, Index, Option<usize>)> {
+        let uri = params.at(&["textDocument", "uri"])?.as_str()?;
+        let code = self.documents.get(uri)?;
+        let index = index(code);
+
+        let line = params.at(&["position", "line"])?.as_f64()? as u32 + 1;
+        let column = params.at(&["position", "character"])?.as_f64()? as u32;
+        let symbol = index.at(line, column);
+        Some((uri.to_string(), index, symbol))
+    }
+
+    fn definition(&self, params: &Json) -> Option<Json> {
+        let (uri, index, symbol) = self.lookup(params)?;
+        let found = symbol.and_then(|s| index.occurrences_of(s).find(|o| o.declaration));
+        Some(found.map_or(Json::Null, |o| location(&uri, o)))
+    }
+
+    fn references(&self, params: &Json) -> Option<Json> {
+        let (uri, index, symbol) = self.lookup(params)?;
+        let declaration = params
+            .at(&["context", "includeDeclaration"])
+            .and_then(Json::as_bool)
+            .unwrap_or(true);
+
+        let locations = match symbol {
+            Some(s) => index
+                .occurrences_of(s)
+                .filter(|o| declaration || !o.declaration)
+                .map(|o| location(&uri, o))
+                .collect(),
+            None => Vec::new(),
+        };
+        Some(Json::Array(locations))
+    }
+
+    fn hover(&self, params: &Json) -> Option<Json> {
+        let (_, index, symbol) = self.lookup(params)?;
+        let symbol = match symbol {
+            Some(s) => &index.symbols[s],
+            None => return Some(Json::Null),
+        };
+
+        let mut value = format!("```lox\n{}\n```", symbol.detail);
+        if let Some(ref doc) = symbol.doc {
+            value.push_str("\n\n");
+            value.push_str(doc);
+        }
+        Some(Json::object(vec![(
+            "contents",
+            Json::object(vec![
+                ("kind", Json::str("markdown")),
+                ("value", Json::String(value)),
+            ]),
+        )]))
+    }
+
+    fn symbols(&self, params: &Json) -> Option<Json> {
+        let uri = params.at(&["textDocument", "uri"])?.as_str()?;
+        let index = index(self.documents.get(uri)?);
+        Some(Json::Array(outline(&index, None)))
+    }
+
+    fn completion(&self, params: &Json) -> Json {
+        let mut items: Vec<(String, f64)> = scanner::keywords()
+            .into_iter()
+            .map(|k| (k.to_string(), 14.0))
+            .collect();
+        items.extend(
+            Interpreter::new(false)
+                .env
+                .names()
+                .into_iter()
+                .map(|n| (n, 3.0)),
+        );
+
+        let code = params
+            .at(&["textDocument", "uri"])
+            .and_then(Json::as_str)
+            .and_then(|uri| self.documents.get(uri));
+        if let Some(code) = code {
+            for symbol in index(code).symbols {
+                let kind = match symbol.kind {
+                    Kind::Function => 3.0,
+                    Kind::Constant => 21.0,
+                    Kind::Variable | Kind::Parameter => 6.0,
+                };
+                items.push((symbol.name, kind));
+            }
+        }
+
+        items.sort_by(|a, b| a.0.cmp(&b.0));
+        items.dedup_by(|a, b| a.0 == b.0);
+        Json::Array(
+            items
+                .into_iter()
+                .map(|(label, kind)| {
+                    Json::object(vec![
+                        ("label", Json::String(label)),
+                        ("kind", Json::Number(kind)),
+                    ])
+                })
+                .collect(),
+        )
+    }
+}
+
+fn capabilities() -> Json {
+    Json::object(vec![
+        (
+            "capabilities",
+            Json::object(vec![
+                ("textDocumentSync", Json::Number(1.0)),
+                ("definitionProvider", Json::Bool(true)),
+                ("referencesProvider", Json::Bool(true)),
+                ("hoverProvider", Json::Bool(true)),
+                ("documentSymbolProvider", Json::Bool(true)),
+                ("completionProvider", Json::object(vec![])),
+            ]),
+        ),
+        (
+            "serverInfo",
+            Json::object(vec![("name", Json::str("rlox"))]),
+        ),
+    ])
+}
+
+fn error(id: Json, code: f64, message: String) -> Json {
+    Json::object(vec![
+        ("jsonrpc", Json::str("2.0")),
+        ("id", id),
+        (
+            "error",
+            Json::object(vec![
+                ("code", Json::Number(code)),
+                ("message", Json::String(message)),
+            ]),
+        ),
+    ])
+}
+
+fn publish(uri: &str, diagnostics: Vec<Json>) -> Json {
+    Json::object(vec![
+        ("jsonrpc", Json::str("2.0")),
+        ("method", Json::str("textDocument/publishDiagnostics")),
+        (
+            "params",
+            Json::object(vec![
+                ("uri", Json::str(uri)),
+                ("diagnostics", Json::Array(diagnostics)),
+            ]),
+        ),
+    ])
+}
+
+/// The problems `rlox check` would report, each underlining the text it is
+/// near, or its whole line when that cannot be found.
+fn diagnostics(code: &str) -> Vec<Json> {
+    check(code)
+        .iter()
+        .filter_map(|err| {
+            let (line, msg, near) = match err {
+                Error::Lexer(l, m, n) | Error::Parser(l, m, n) | Error::Resolve(l, m, n) => {
+                    (*l, m, n)
+                }
+                _ => return None,
+            };
+
+            let text = code
+                .lines()
+                .nth(line.saturating_sub(1) as usize)
+                .unwrap_or("");
+            let (column, len) = match text.find(near.as_str()) {
+                Some(at) if !near.is_empty() => (text[..at].chars().count(), near.chars().count()),
+                _ => (0, text.chars().count()),
+            };
+
+            Some(Json::object(vec![
+                ("range", range(line.max(1), column as u32, len as u32)),
+                ("severity", Json::Number(1.0)),
+                ("source", Json::str("rlox")),
+                ("message", Json::String(msg.clone())),
+            ]))
+        })
+        .collect()
+}
+
+/// Indexes whatever statements of `code` parse.
+fn index(code: &str) -> Index {
+    let mut scanner = Scanner::new(code.to_string());
+    scanner.scan_tokens();
+    let stmts: Vec<_> = scanner.statements().filter_map(Result::ok).collect();
+    Index::new(&stmts)
+}
+
+/// Document symbols declared directly in `parent`, with the symbols of each
+/// function nested under it.
+fn outline(index: &Index, parent: Option<usize>) -> Vec<Json> {
+    index
+        .symbols
+        .iter()
+        .enumerate()
+        .filter(|(_, s)| s.parent == parent && s.kind != Kind::Parameter)
+        .map(|(i, s)| {
+            let kind = match s.kind {
+                Kind::Function => 12.0,
+                Kind::Constant => 14.0,
+                Kind::Variable | Kind::Parameter => 13.0,
+            };
+            let name = range(s.line, s.column, s.name.chars().count() as u32);
+            Json::object(vec![
+                ("name", Json::String(s.name.clone())),
+                ("detail", Json::String(s.detail.clone())),
+                ("kind", Json::Number(kind)),
+                ("range", name.clone()),
+                ("selectionRange", name),
+                ("children", Json::Array(outline(index, Some(i)))),
+            ])
+        })
+        .collect()
+}
@@ -1,5 +1,6 @@
 use crate::{function::Callable, token::Literal};
 use std::{
+    cell::RefCell,
     cmp::{self, Ordering},
     fmt,
     rc::Rc,
@@ -9,10 +10,29 @@ use std::{
 pub enum Object {
     Literal(Literal),
     Func(Rc<dyn Callable>),
+    List(Rc<RefCell<Vec<Object>>>),
 }
 
 impl Object {
+    pub fn list(items: Vec<Object>) -> Object {
+        Object::List(Rc::new(RefCell::new(items)))
+    }
+
+    /// Lox truthiness: only `nil` and `false` are falsey.
     pub fn is_truthy(&self) -> bool {
+        match self {
+            Object::Literal(l) => match l {
+                Literal::False => return false,
+                Literal::None => return false,
+                _ => return true,
+            },
+            Object::Func(_) | Object::List(_) => true,
+        }
+    }
+
+    /// Pre-spec truthiness where `0` and `""` are also falsey, kept for
+    /// scripts run in compatibility mode.
+    pub fn is_truthy_compat(&self) -> bool {
         match self {
             Object::Literal(l) => match l {
                 Literal::True => return true,
@@ -22,6 +42,20 @@ impl Object {
                 Literal::StringLit(s) => return !s.is_empty(),
             },
             Object::Func(_) => true,
+            Object::List(l) => !l.borrow().is_empty(),
+        }
+    }
+
+    /// The value as it would be written in Lox, so strings stand out from
+    /// other values in messages.
+    pub fn repr(&self) -> String {
+        match self {
+            Object::Literal(Literal::StringLit(s)) => format!("{:?}", s),
+            Object::List(items) => {
+                let items: Vec<String> = items.borrow().iter().map(Object::repr).collect();
+                format!("[{}]", items.join(", "))
+            }
+            value => value.to_string(),
         }
     }
 }
@@ -30,6 +64,8 @@ impl cmp::PartialEq for Object {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (&Object::Literal(ref lhs), &Object::Literal(ref rhs)) => lhs.eq(rhs),
+            (&Object::Func(ref lhs), &Object::Func(ref rhs)) => Rc::ptr_eq(lhs, rhs),
+            (&Object::List(ref lhs), &Object::List(ref rhs)) => lhs.eq(rhs),
 
             _ => false,
         }
@@ -49,7 +85,21 @@ impl fmt::Debug for Object {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Object::Literal(ref lit) => lit.fmt(f),
-            Object::Func(_) => write!(f, "<function>"),
+            Object::Func(ref c) => write!(f, "<fn {}>", c.name()),
+            Object::List(ref l) => f.debug_list().entries(l.borrow().iter()).finish(),
+        }
+    }
+}
+
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Object::Literal(ref lit) => write!(f, "{}", lit),
+            Object::Func(ref c) => write!(f, "<fn {}>", c.name()),
+            Object::List(ref l) => {
+                let items: Vec<String> = l.borrow().iter().map(|o| o.to_string()).collect();
+                write!(f, "[{}]", items.join(", "))
+            }
         }
     }
 }
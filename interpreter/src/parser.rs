@@ -3,18 +3,69 @@ use std::rc::Rc;
 use crate::{
     error::{Error, ResultMSG},
     expr::{self, Boxed, Expr},
+    function::{Param, Signature},
     stmt::Stmt,
-    token::{self, Token, TokenType, *},
+    token::{Token, TokenType, *},
 };
 
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    incomplete: bool,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Parser { tokens, current: 0 }
+        Parser {
+            tokens: Parser::attach_docs(tokens),
+            current: 0,
+            incomplete: false,
+        }
+    }
+
+    /// Whether the last error was caused by running out of tokens, meaning
+    /// more input could still complete the statement.
+    pub fn is_incomplete(&self) -> bool {
+        self.incomplete
+    }
+
+    /// Keeps doc comments only where they precede a declaration, so the rest
+    /// of the grammar never has to skip over them.
+    fn attach_docs(tokens: Vec<Token>) -> Vec<Token> {
+        let mut keep = vec![true; tokens.len()];
+        let mut i = 0;
+
+        while i < tokens.len() {
+            if tokens[i].token_type != TokenType::DOC_COMMENT {
+                i += 1;
+                continue;
+            }
+
+            let start = i;
+            while i < tokens.len() && tokens[i].token_type == TokenType::DOC_COMMENT {
+                i += 1;
+            }
+
+            let documents = i < tokens.len()
+                && tokens[i].in_types(vec![
+                    TokenType::FUN,
+                    TokenType::VAR,
+                    TokenType::LET,
+                    TokenType::CONST,
+                    TokenType::CLASS,
+                ]);
+
+            if !documents {
+                keep[start..i].iter_mut().for_each(|k| *k = false);
+            }
+        }
+
+        tokens
+            .into_iter()
+            .zip(keep)
+            .filter(|(_, k)| *k)
+            .map(|(t, _)| t)
+            .collect()
     }
 
     pub fn parse(&mut self) -> ResultMSG<Stmt> {
@@ -26,22 +77,101 @@ impl Parser {
     }
 
     fn assignment(&mut self) -> ResultMSG<Expr> {
-        let expr = self.equality()?;
+        let expr = self.conditional()?;
 
         if self.match_tok(vec![TokenType::EQUAL]) {
             return match expr {
                 Expr::Identifier(id) => Ok(Expr::Assignment(id, self.assignment()?.boxed())),
                 _ => Err(Error::Parser(
-                    self.peek().line,
-                    "Unexpected Token".to_string(),
-                    format!("{:?}", self.peek()),
+                    self.previous().line,
+                    "Invalid assignment target".to_string(),
+                    self.previous().lexeme,
                 )),
             };
         }
 
+        if let Some(op_type) = self.peek().token_type.compound_op() {
+            let assign = self.advance();
+            let op = Token {
+                token_type: op_type,
+                lexeme: assign.lexeme.trim_end_matches('=').to_string(),
+                literal: Literal::None,
+                line: assign.line,
+                column: assign.column,
+            };
+
+            return match expr {
+                Expr::Identifier(id) => Ok(Expr::Compound(id, op, self.assignment()?.boxed())),
+                target => Err(invalid_target(&target, assign)),
+            };
+        }
+
         Ok(expr)
     }
 
+    fn conditional(&mut self) -> ResultMSG<Expr> {
+        let cond: Expr = self.coalesce()?;
+
+        if !self.match_tok(vec![TokenType::QUESTION]) {
+            return Ok(cond);
+        }
+
+        let then_expr: Expr = self.expression()?;
+        if !self.match_tok(vec![TokenType::COLON]) {
+            return Err(Error::Parser(
+                self.peek().line,
+                "Expect ':' in conditional expression".to_string(),
+                self.peek().lexeme,
+            ));
+        }
+        let else_expr: Expr = self.conditional()?;
+
+        Ok(Expr::Conditional(
+            cond.boxed(),
+            then_expr.boxed(),
+            else_expr.boxed(),
+        ))
+    }
+
+    fn coalesce(&mut self) -> ResultMSG<Expr> {
+        let mut expr: Expr = self.or()?;
+
+        while self.match_tok(vec![TokenType::QUESTION_QUESTION]) {
+            let operator = self.previous();
+            let right: Expr = self.or()?;
+
+            expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
+        }
+
+        return Ok(expr);
+    }
+
+    fn or(&mut self) -> ResultMSG<Expr> {
+        let mut expr: Expr = self.and()?;
+
+        while self.match_tok(vec![TokenType::OR]) {
+            let operator = self.previous();
+            let right: Expr = self.and()?;
+
+            expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
+        }
+
+        return Ok(expr);
+    }
+
+    fn and(&mut self) -> ResultMSG<Expr> {
+        let mut expr: Expr = self.equality()?;
+
+        while self.match_tok(vec![TokenType::AND]) {
+            let operator = self.previous();
+            let right: Expr = self.equality()?;
+
+            expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
+        }
+
+        return Ok(expr);
+    }
+
     fn equality(&mut self) -> ResultMSG<Expr> {
         let mut expr: Expr = self.comparision()?;
 
@@ -55,7 +185,7 @@ impl Parser {
         return Ok(expr);
     }
     fn comparision(&mut self) -> ResultMSG<Expr> {
-        let mut expr: Expr = self.term()?;
+        let mut expr: Expr = self.bit_or()?;
 
         while self.match_tok(vec![
             TokenType::GREATER,
@@ -63,6 +193,58 @@ impl Parser {
             TokenType::LESS,
             TokenType::LESS_EQUAL,
         ]) {
+            let operator = self.previous();
+            let right: Expr = self.bit_or()?;
+
+            expr = Expr::Binary(Box::new(expr), operator, Box::new(right))
+        }
+
+        return Ok(expr);
+    }
+
+    fn bit_or(&mut self) -> ResultMSG<Expr> {
+        let mut expr: Expr = self.bit_xor()?;
+
+        while self.match_tok(vec![TokenType::PIPE]) {
+            let operator = self.previous();
+            let right: Expr = self.bit_xor()?;
+
+            expr = Expr::Binary(Box::new(expr), operator, Box::new(right))
+        }
+
+        return Ok(expr);
+    }
+
+    fn bit_xor(&mut self) -> ResultMSG<Expr> {
+        let mut expr: Expr = self.bit_and()?;
+
+        while self.match_tok(vec![TokenType::CARET]) {
+            let operator = self.previous();
+            let right: Expr = self.bit_and()?;
+
+            expr = Expr::Binary(Box::new(expr), operator, Box::new(right))
+        }
+
+        return Ok(expr);
+    }
+
+    fn bit_and(&mut self) -> ResultMSG<Expr> {
+        let mut expr: Expr = self.shift()?;
+
+        while self.match_tok(vec![TokenType::AMPERSAND]) {
+            let operator = self.previous();
+            let right: Expr = self.shift()?;
+
+            expr = Expr::Binary(Box::new(expr), operator, Box::new(right))
+        }
+
+        return Ok(expr);
+    }
+
+    fn shift(&mut self) -> ResultMSG<Expr> {
+        let mut expr: Expr = self.term()?;
+
+        while self.match_tok(vec![TokenType::LESS_LESS, TokenType::GREATER_GREATER]) {
             let operator = self.previous();
             let right: Expr = self.term()?;
 
@@ -87,7 +269,12 @@ impl Parser {
     fn factor(&mut self) -> ResultMSG<Expr> {
         let mut expr: Expr = self.unary()?;
 
-        while self.match_tok(vec![TokenType::SLASH, TokenType::STAR]) {
+        while self.match_tok(vec![
+            TokenType::SLASH,
+            TokenType::STAR,
+            TokenType::PERCENT,
+            TokenType::TILDE_SLASH,
+        ]) {
             let operator = self.previous();
             let right: Expr = self.unary()?;
 
@@ -98,7 +285,16 @@ impl Parser {
     }
 
     fn unary(&mut self) -> ResultMSG<Expr> {
-        if self.match_tok(vec![TokenType::BANG, TokenType::MINUS]) {
+        if self.match_tok(vec![TokenType::PLUS_PLUS, TokenType::MINUS_MINUS]) {
+            let operator = self.previous();
+
+            return match self.unary()? {
+                Expr::Identifier(id) => Ok(Expr::Update(id, operator, true)),
+                target => Err(invalid_target(&target, operator)),
+            };
+        }
+
+        if self.match_tok(vec![TokenType::BANG, TokenType::MINUS, TokenType::TILDE]) {
             let operator = self.previous();
             let right: Expr = self.unary()?;
 
@@ -107,15 +303,65 @@ impl Parser {
             return Ok(expr);
         }
 
-        return self.call();
+        return self.power();
+    }
+
+    fn power(&mut self) -> ResultMSG<Expr> {
+        let expr: Expr = self.postfix()?;
+
+        if self.match_tok(vec![TokenType::STAR_STAR]) {
+            let operator = self.previous();
+            let right: Expr = self.unary()?;
+
+            return Ok(Expr::Binary(Box::new(expr), operator, Box::new(right)));
+        }
+
+        return Ok(expr);
+    }
+
+    fn postfix(&mut self) -> ResultMSG<Expr> {
+        let expr: Expr = self.call()?;
+
+        if self.match_tok(vec![TokenType::PLUS_PLUS, TokenType::MINUS_MINUS]) {
+            let operator = self.previous();
+
+            return match expr {
+                Expr::Identifier(id) => Ok(Expr::Update(id, operator, false)),
+                target => Err(invalid_target(&target, operator)),
+            };
+        }
+
+        Ok(expr)
     }
 
     fn call(&mut self) -> ResultMSG<Expr> {
         let mut expr = self.primary()?;
+        // once a `?.` short-circuits, the rest of the property chain must too
+        let mut safe = false;
 
         loop {
-            expr = match self.match_tok(vec![TokenType::LEFT_PAREN]) {
-                true => self.finish_call(expr)?,
+            expr = match self.match_tok(vec![
+                TokenType::LEFT_PAREN,
+                TokenType::LEFT_BRACKET,
+                TokenType::DOT,
+                TokenType::QUESTION_DOT,
+            ]) {
+                true => match self.previous().token_type {
+                    TokenType::LEFT_PAREN => self.finish_call(expr)?,
+                    TokenType::LEFT_BRACKET => self.finish_index(expr)?,
+                    token_type => {
+                        safe = safe || token_type == TokenType::QUESTION_DOT;
+                        let name = self.advance();
+                        if name.token_type != TokenType::IDENTIFIER {
+                            return Err(Error::Parser(
+                                name.line,
+                                "Expect property name after '.'".to_string(),
+                                name.lexeme,
+                            ));
+                        }
+                        Expr::Get(expr.boxed(), name, safe)
+                    }
+                },
                 _ => break,
             };
         }
@@ -125,49 +371,76 @@ impl Parser {
 
     fn finish_call(&mut self, callee: Expr) -> ResultMSG<Expr> {
         let mut args: Vec<Expr> = Vec::new();
+        let mut named: Vec<(Token, Expr)> = Vec::new();
+
         if !self.check(TokenType::RIGHT_PAREN) {
             loop {
-                if args.len() >= 255 {
+                let is_named = self.check(TokenType::IDENTIFIER)
+                    && matches!(self.peek_next(), Ok(t) if t.token_type == TokenType::COLON);
+
+                if is_named {
+                    let name = self.advance();
+                    self.advance();
+                    if named.iter().any(|(n, _)| n.lexeme == name.lexeme) {
+                        return Err(Error::Parser(
+                            name.line,
+                            "Duplicate named argument".to_string(),
+                            name.lexeme,
+                        ));
+                    }
+                    named.push((name, self.expression()?));
+                } else if !named.is_empty() {
                     return Err(Error::Parser(
-                        0,
-                        "cannot have more than 255 arguments".to_string(),
-                        "".to_string(),
+                        self.peek().line,
+                        "Positional argument after named argument".to_string(),
+                        self.peek().lexeme,
                     ));
+                } else {
+                    args.push(self.expression()?);
                 }
 
-                args.push(self.expression()?);
-
                 if !self.match_tok(vec![TokenType::COMMA]) {
                     break;
                 }
             }
-            self.current -= 1;
         }
 
         Ok(Expr::Call(
             callee.boxed(),
-            self.consume(TokenType::RIGHT_PAREN, "Expect ')' after arguments."),
+            self.consume(TokenType::RIGHT_PAREN, "Expect ')' after arguments.")?,
             args,
+            named,
         ))
     }
 
+    fn finish_index(&mut self, object: Expr) -> ResultMSG<Expr> {
+        let bracket = self.previous();
+        let index = self.expression()?;
+        self.consume(TokenType::RIGHT_BRACKET, "Expect ']' after index.")?;
+
+        Ok(Expr::Index(object.boxed(), bracket, index.boxed()))
+    }
+
     fn primary(&mut self) -> ResultMSG<Expr> {
         if self.match_tok(vec![TokenType::IDENTIFIER]) {
-            return Ok(Expr::Identifier(self.previous().lexeme.clone()));
+            return Ok(Expr::Identifier(self.previous()));
         }
 
         if self.match_tok(vec![TokenType::FALSE, TokenType::TRUE, TokenType::NIL]) {
-            self.advance();
-            return Ok(Expr::Literal(self.peek()));
+            return Ok(Expr::Literal(self.previous()));
         }
 
         if self.match_tok(vec![TokenType::NUMBER, TokenType::STRING]) {
             return Ok(Expr::Literal(self.previous()));
         }
 
+        if self.match_tok(vec![TokenType::INTERPOLATION]) {
+            return self.interpolation();
+        }
+
         if self.match_tok(vec![TokenType::LEFT_PAREN]) {
             let expr = self.expression()?;
-            self.consume(TokenType::RIGHT_PAREN, "Expect ')' after expression");
+            self.consume(TokenType::RIGHT_PAREN, "Expect ')' after expression")?;
             return Ok(Expr::Grouping(Box::new(expr)));
         }
 
@@ -178,6 +451,29 @@ impl Parser {
         ));
     }
 
+    fn interpolation(&mut self) -> ResultMSG<Expr> {
+        let mut parts: Vec<Expr> = vec![Expr::Literal(self.previous())];
+
+        loop {
+            parts.push(self.expression()?);
+
+            if self.match_tok(vec![TokenType::INTERPOLATION]) {
+                parts.push(Expr::Literal(self.previous()));
+            } else if self.match_tok(vec![TokenType::STRING]) {
+                parts.push(Expr::Literal(self.previous()));
+                break;
+            } else {
+                return Err(Error::Parser(
+                    self.peek().line,
+                    "Expect '}' after interpolated expression".to_string(),
+                    self.peek().lexeme,
+                ));
+            }
+        }
+
+        Ok(Expr::Interpolation(parts))
+    }
+
     fn match_tok(&mut self, tokens: Vec<TokenType>) -> bool {
         for token in tokens {
             if self.check(token) {
@@ -196,23 +492,6 @@ impl Parser {
         return self.peek().token_type == token;
     }
 
-    fn check_types(&mut self, types: &[TokenType]) -> bool {
-        if self.is_at_end() {
-            return false;
-        }
-        match self.peek() {
-            t => t.in_types(types.to_vec()),
-            _ => false,
-        }
-    }
-
-    fn check_next(&mut self, types: &[TokenType]) -> Option<ResultMSG<Token>> {
-        if self.check_types(types) {
-            return Some(self.peek_next());
-        }
-        None
-    }
-
     fn advance(&mut self) -> Token {
         if !self.is_at_end() {
             self.current += 1;
@@ -244,23 +523,18 @@ impl Parser {
         return self.tokens[self.current - 1].clone();
     }
 
-    fn consume(&mut self, token: TokenType, message: &str) -> Token {
+    fn consume(&mut self, token: TokenType, message: &str) -> ResultMSG<Token> {
         if self.check(token) {
-            return self.advance();
-        }
-        self.error(self.peek(), message);
-        return Token {
-            token_type: token,
-            lexeme: "".to_string(),
-            literal: token::Literal::None,
-            line: 0,
-        };
+            return Ok(self.advance());
+        }
+        Err(self.error(self.peek(), message))
     }
-    fn error(&self, token: Token, message: &str) {
+
+    fn error(&self, token: Token, message: &str) -> Error {
         if token.token_type == TokenType::EOF {
-            println!("{} at end {}", token.line, message);
+            Error::Parser(token.line, message.to_string(), "end of input".to_string())
         } else {
-            println!("{} at '{:?}' {}", token.line, token.lexeme, message);
+            Error::Parser(token.line, message.to_string(), token.lexeme)
         }
     }
     fn synchronize(&mut self) {
@@ -274,6 +548,8 @@ impl Parser {
                 TokenType::CLASS
                 | TokenType::FUN
                 | TokenType::VAR
+                | TokenType::LET
+                | TokenType::CONST
                 | TokenType::FOR
                 | TokenType::IF
                 | TokenType::WHILE
@@ -287,10 +563,21 @@ impl Parser {
     }
 
     fn statement(&mut self) -> ResultMSG<Stmt> {
+        if self.check(TokenType::DOC_COMMENT) {
+            let mut docs: Vec<Token> = Vec::new();
+            while self.match_tok(vec![TokenType::DOC_COMMENT]) {
+                docs.push(self.previous());
+            }
+
+            return Ok(Stmt::Doc(docs, self.statement()?.boxed()));
+        }
+
         let v = self.match_tok(vec![
             TokenType::SEMICOLON,
             TokenType::PRINT,
             TokenType::VAR,
+            TokenType::LET,
+            TokenType::CONST,
             TokenType::LEFT_BRACE,
             TokenType::IF,
             TokenType::WHILE,
@@ -315,7 +602,8 @@ impl Parser {
         match token.token_type {
             TokenType::SEMICOLON => Ok(Stmt::Empty),
             TokenType::PRINT => self.print_statement(),
-            TokenType::VAR => self.declaration_statement(),
+            TokenType::VAR | TokenType::LET => self.declaration_statement(),
+            TokenType::CONST => self.const_statement(),
             TokenType::LEFT_BRACE => self.block_statement(),
             TokenType::IF => self.if_statement(),
             TokenType::WHILE => self.while_statement(),
@@ -329,52 +617,69 @@ impl Parser {
 
     fn print_statement(&mut self) -> ResultMSG<Stmt> {
         let expr: Expr = self.expression()?;
-        self.consume(TokenType::SEMICOLON, "Expect ';' after value.");
+        self.consume(TokenType::SEMICOLON, "Expect ';' after value.")?;
         Ok(Stmt::Print(expr))
     }
 
     fn expr_statement(&mut self) -> ResultMSG<Stmt> {
         let expr: Expr = self.expression()?;
+        self.consume(TokenType::SEMICOLON, "Expect ';' after expression.")?;
         Ok(Stmt::Expression(expr))
     }
 
     fn declaration_statement(&mut self) -> ResultMSG<Stmt> {
-        let id: Token = self.consume(TokenType::IDENTIFIER, "Expect variable name.");
+        let id: Token = self.consume(TokenType::IDENTIFIER, "Expect variable name.")?;
+
+        if !self.match_tok(vec![TokenType::EQUAL]) {
+            self.consume(TokenType::SEMICOLON, "Expect ';' after variable name.")?;
+            return Ok(Stmt::Declaration(id, None));
+        }
+
+        let expr: Expr = self.expression()?;
+
+        self.consume(TokenType::SEMICOLON, "Expect ';' after value.")?;
+
+        Ok(Stmt::Declaration(id, Some(expr)))
+    }
+
+    fn const_statement(&mut self) -> ResultMSG<Stmt> {
+        let id: Token = self.consume(TokenType::IDENTIFIER, "Expect constant name.")?;
 
         if !self.match_tok(vec![TokenType::EQUAL]) {
-            return Ok(Stmt::Declaration(id.lexeme, None));
+            return Err(Error::Parser(
+                id.line,
+                "Constants must be initialized".to_string(),
+                id.lexeme,
+            ));
         }
 
         let expr: Expr = self.expression()?;
 
-        self.consume(TokenType::SEMICOLON, "Expect ';' after value.");
+        self.consume(TokenType::SEMICOLON, "Expect ';' after value.")?;
 
-        Ok(Stmt::Declaration(id.lexeme, Some(expr)))
+        Ok(Stmt::Const(id, expr))
     }
 
     fn block_statement(&mut self) -> ResultMSG<Stmt> {
         let mut stmts: Vec<Stmt> = Vec::new();
 
-        while self.check_next(&[TokenType::RIGHT_BRACE]).is_none() {
+        while !self.check(TokenType::RIGHT_BRACE) && !self.is_at_end() {
             stmts.push(self.statement()?);
         }
 
-        self.consume(TokenType::RIGHT_BRACE, "Expect '}' after block");
+        self.consume(TokenType::RIGHT_BRACE, "Expect '}' after block")?;
 
         Ok(Stmt::Block(stmts))
     }
 
     fn if_statement(&mut self) -> ResultMSG<Stmt> {
-        self.check_next(&[TokenType::LEFT_PAREN]);
         let expr: Expr = self.expression()?;
-        self.check_next(&[TokenType::RIGHT_PAREN]);
 
         let then_stmt: Box<Stmt> = self.statement()?.boxed();
 
-        match self.check_next(&[TokenType::ELSE]) {
-            Some(Err(e)) => Err(e),
-            Some(Ok(_)) => Ok(Stmt::If(expr, then_stmt, Some(self.statement()?.boxed()))),
-            None => Ok(Stmt::If(expr, then_stmt, None)),
+        match self.match_tok(vec![TokenType::ELSE]) {
+            true => Ok(Stmt::If(expr, then_stmt, Some(self.statement()?.boxed()))),
+            false => Ok(Stmt::If(expr, then_stmt, None)),
         }
     }
 
@@ -384,26 +689,19 @@ impl Parser {
         Ok(Stmt::While(expr, body))
     }
     fn for_statement(&mut self) -> ResultMSG<Stmt> {
-        self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'for'.");
-
-        let init: Option<Stmt> = match self.match_tok(vec![TokenType::SEMICOLON, TokenType::VAR]) {
-            true => match self.previous().token_type {
-                TokenType::SEMICOLON => None,
-                TokenType::VAR => {
-                    let dec = self.declaration_statement()?;
-                    self.consume(
-                        TokenType::SEMICOLON,
-                        "Expect ';' after loop initialization.",
-                    );
-
-                    Some(dec)
-                }
-                _ => {
-                    unreachable!()
-                }
-            },
-            _ => Some(self.expr_statement()?),
-        };
+        self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'for'.")?;
+
+        let init: Option<Stmt> =
+            match self.match_tok(vec![TokenType::SEMICOLON, TokenType::VAR, TokenType::LET]) {
+                true => match self.previous().token_type {
+                    TokenType::SEMICOLON => None,
+                    TokenType::VAR | TokenType::LET => Some(self.declaration_statement()?),
+                    _ => {
+                        unreachable!()
+                    }
+                },
+                _ => Some(self.expr_statement()?),
+            };
 
         let cond: Expr = match self.match_tok(vec![TokenType::SEMICOLON]) {
             true => Expr::Literal(Token {
@@ -411,11 +709,12 @@ impl Parser {
                 lexeme: "".to_string(),
                 literal: Literal::True,
                 line: self.peek().line,
+                column: self.peek().column,
             }),
 
             false => {
                 let expr = self.expression()?;
-                self.consume(TokenType::SEMICOLON, "Expect ';' after loop condition.");
+                self.consume(TokenType::SEMICOLON, "Expect ';' after loop condition.")?;
                 expr
             }
         };
@@ -423,10 +722,10 @@ impl Parser {
         let inc: Option<Stmt> = match self.match_tok(vec![TokenType::RIGHT_PAREN]) {
             true => None,
             false => {
-                let expr = self.expr_statement()?;
+                let expr = self.expression()?;
 
-                self.consume(TokenType::RIGHT_PAREN, "Expect ')' after loop.");
-                Some(expr)
+                self.consume(TokenType::RIGHT_PAREN, "Expect ')' after loop.")?;
+                Some(Stmt::Expression(expr))
             }
         };
 
@@ -446,57 +745,78 @@ impl Parser {
     }
 
     fn break_statement(&mut self) -> ResultMSG<Stmt> {
-        match self.check_next(&[TokenType::SEMICOLON]) {
-            Some(token) => match token {
-                Ok(t) => Ok(Stmt::Break(t.line)),
-                Err(e) => Err(e),
-            },
-            None => unreachable!(),
-        }
+        let line = self.previous().line;
+        self.consume(TokenType::SEMICOLON, "Expect ';' after 'break'.")?;
+        Ok(Stmt::Break(line))
     }
 
     fn function_statement(&mut self) -> ResultMSG<Stmt> {
-        let name: Token = self.consume(
-            TokenType::IDENTIFIER,
-            &format!("Expect {} name. ", self.peek().lexeme),
-        );
-        self.consume(
-            TokenType::LEFT_PAREN,
-            &format!("Expect '(' after {} name.", self.peek().lexeme),
-        );
+        let name: Token = self.consume(TokenType::IDENTIFIER, "Expect function name.")?;
+        self.consume(TokenType::LEFT_PAREN, "Expect '(' after function name.")?;
 
-        let mut params: Vec<String> = Vec::new();
+        let mut params: Vec<Param> = Vec::new();
 
         if !self.check(TokenType::RIGHT_PAREN) {
             loop {
-                if params.len() >= 8 {
+                let rest = self.match_tok(vec![TokenType::ELLIPSIS]);
+                let param = self.consume(TokenType::IDENTIFIER, "Expect parameter name.")?;
+
+                if params.iter().any(|p| p.name == param.lexeme) {
                     return Err(Error::Parser(
-                        name.line,
-                        "cannot have more than 8 arguments".to_string(),
-                        name.lexeme,
+                        param.line,
+                        "Duplicate parameter name".to_string(),
+                        param.lexeme,
                     ));
                 }
 
-                params.push(
-                    self.consume(TokenType::IDENTIFIER, "Expect parameter name.")
-                        .lexeme,
-                );
+                let default = match self.match_tok(vec![TokenType::EQUAL]) {
+                    true if rest => {
+                        return Err(Error::Parser(
+                            param.line,
+                            "Rest parameter cannot have a default value".to_string(),
+                            param.lexeme,
+                        ))
+                    }
+                    true => Some(self.expression()?),
+                    false => None,
+                };
+
+                if default.is_none() && !rest && params.iter().any(|p| p.default.is_some()) {
+                    return Err(Error::Parser(
+                        param.line,
+                        "Required parameter after parameter with default value".to_string(),
+                        param.lexeme,
+                    ));
+                }
+
+                params.push(Param {
+                    name: param.lexeme.clone(),
+                    default,
+                    rest,
+                    line: param.line,
+                    column: param.column,
+                });
 
-                if self.check_next(&[TokenType::COMMA]).is_none() {
+                if rest && !self.check(TokenType::RIGHT_PAREN) {
+                    return Err(Error::Parser(
+                        param.line,
+                        "Rest parameter must be the last parameter".to_string(),
+                        param.lexeme,
+                    ));
+                }
+
+                if !self.match_tok(vec![TokenType::COMMA]) {
                     break;
                 }
             }
         }
 
-        self.consume(TokenType::RIGHT_PAREN, "Expect ')' after parameters.");
-        self.consume(
-            TokenType::LEFT_BRACE,
-            &format!("Expect '{{' before {} body", self.peek().lexeme),
-        );
+        self.consume(TokenType::RIGHT_PAREN, "Expect ')' after parameters.")?;
+        self.consume(TokenType::LEFT_BRACE, "Expect '{' before function body.")?;
 
         Ok(Stmt::Function(
-            name.lexeme,
-            params,
+            name,
+            Rc::new(Signature::new(params)),
             Rc::new(self.block_statement()?),
         ))
     }
@@ -510,12 +830,13 @@ impl Parser {
                 lexeme: "nil".to_string(),
                 literal: Literal::None,
                 line: self.peek().line,
+                column: self.peek().column,
             })
         } else {
             self.expression()?
         };
 
-        self.consume(TokenType::SEMICOLON, "Expect ';' after return value.");
+        self.consume(TokenType::SEMICOLON, "Expect ';' after return value.")?;
 
         Ok(Stmt::Return(ln, expr))
     }
@@ -531,9 +852,20 @@ impl Iterator for Parser {
 
         let res = self.statement();
         if res.is_err() {
+            self.incomplete = self.is_at_end();
             self.synchronize();
         }
 
         Some(res)
     }
 }
+
+/// The error for `op` updating something other than a variable. Lists can
+/// be indexed but not changed, so `xs[i] += 1` and `xs[i]++` are rejected.
+fn invalid_target(target: &Expr, op: Token) -> Error {
+    let msg = match target {
+        Expr::Index(..) => "Invalid assignment target, list elements cannot be changed",
+        _ => "Invalid assignment target",
+    };
+    Error::Parser(op.line, msg.to_string(), op.lexeme)
+}
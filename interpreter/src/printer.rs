@@ -0,0 +1,157 @@
+use std::rc::Rc;
+
+use crate::{
+    expr::{Expr, Visitor as ExprVisitor},
+    function::Signature,
+    stmt::{Stmt, Visitor as StmtVisitor},
+    token::{Literal, Token},
+};
+
+/// Renders statements and expressions as parenthesized prefix notation, such
+/// as `(print (+ 1 (* 2 3)))`, to show how the parser grouped them.
+pub struct AstPrinter;
+
+impl AstPrinter {
+    pub fn stmt(s: &Stmt) -> String {
+        s.accept(&mut AstPrinter)
+    }
+
+    fn parenthesize(&mut self, name: &str, parts: &[String]) -> String {
+        if parts.is_empty() {
+            return format!("({})", name);
+        }
+        format!("({} {})", name, parts.join(" "))
+    }
+}
+
+impl ExprVisitor<String> for AstPrinter {
+    fn visit_expr(&mut self, expr: &Expr) -> String {
+        match *expr {
+            Expr::Identifier(ref name) => name.lexeme.clone(),
+            Expr::Literal(ref lit) => match lit.literal {
+                Literal::StringLit(ref s) => format!("{:?}", s),
+                ref l => l.to_string(),
+            },
+            Expr::Grouping(ref inside) => {
+                let inside = inside.accept(self);
+                self.parenthesize("group", &[inside])
+            }
+            Expr::Unary(ref op, ref rhs) => {
+                let rhs = rhs.accept(self);
+                self.parenthesize(&op.lexeme, &[rhs])
+            }
+            Expr::Binary(ref lhs, ref op, ref rhs) => {
+                let parts = [lhs.accept(self), rhs.accept(self)];
+                self.parenthesize(&op.lexeme, &parts)
+            }
+            Expr::Assignment(ref id, ref rhs) => {
+                let parts = [id.lexeme.clone(), rhs.accept(self)];
+                self.parenthesize("=", &parts)
+            }
+            Expr::Compound(ref id, ref op, ref rhs) => {
+                let parts = [id.lexeme.clone(), rhs.accept(self)];
+                self.parenthesize(&format!("{}=", op.lexeme), &parts)
+            }
+            Expr::Update(ref id, ref op, prefix) => {
+                let name = format!("{}{}", if prefix { "pre" } else { "post" }, op.lexeme);
+                self.parenthesize(&name, &[id.lexeme.clone()])
+            }
+            Expr::Call(ref callee, _, ref args, ref named) => {
+                let mut parts = vec![callee.accept(self)];
+                parts.extend(args.iter().map(|a| a.accept(self)));
+                parts.extend(
+                    named
+                        .iter()
+                        .map(|(n, a)| format!("{}: {}", n.lexeme, a.accept(self))),
+                );
+                self.parenthesize("call", &parts)
+            }
+            Expr::Index(ref object, _, ref index) => {
+                let parts = [object.accept(self), index.accept(self)];
+                self.parenthesize("index", &parts)
+            }
+            Expr::Interpolation(ref parts) => {
+                let parts: Vec<String> = parts.iter().map(|p| p.accept(self)).collect();
+                self.parenthesize("interpolate", &parts)
+            }
+            Expr::Conditional(ref c, ref t, ref e) => {
+                let parts = [c.accept(self), t.accept(self), e.accept(self)];
+                self.parenthesize("?:", &parts)
+            }
+            Expr::Get(ref object, ref name, safe) => {
+                let parts = [object.accept(self), name.lexeme.clone()];
+                self.parenthesize(if safe { "?." } else { "." }, &parts)
+            }
+        }
+    }
+}
+
+impl StmtVisitor<String> for AstPrinter {
+    fn visit_stmt(&mut self, s: &Stmt) -> String {
+        match *s {
+            Stmt::Empty => self.parenthesize("empty", &[]),
+            Stmt::Break(l) => self.visit_break(l),
+            Stmt::Print(ref e) => self.visit_print_stmt(e),
+            Stmt::Expression(ref e) => self.visit_expression_stmt(e),
+            Stmt::Block(ref ss) => self.visit_block_stmt(ss),
+            Stmt::Declaration(ref n, ref e) => self.visit_declaration_stmt(n, e.as_ref()),
+            Stmt::Const(ref n, ref e) => self.visit_const_stmt(n, e),
+            Stmt::If(ref c, ref t, ref e) => self.visit_if(c, t, e.as_deref()),
+            Stmt::While(ref e, ref b) => self.visit_while(e, b),
+            Stmt::Return(l, ref e) => self.visit_return(l, e),
+            Stmt::Function(ref n, ref s, ref b) => self.visit_function(n, s.clone(), b.clone()),
+            Stmt::Doc(_, ref s) => self.visit_stmt(s),
+        }
+    }
+
+    fn visit_block_stmt(&mut self, statements: &Vec<Stmt>) -> String {
+        let parts: Vec<String> = statements.iter().map(|s| s.accept(self)).collect();
+        self.parenthesize("block", &parts)
+    }
+
+    fn visit_expression_stmt(&mut self, expression: &Expr) -> String {
+        let e = expression.accept(self);
+        self.parenthesize("expr", &[e])
+    }
+
+    fn visit_print_stmt(&mut self, expression: &Expr) -> String {
+        let e = expression.accept(self);
+        self.parenthesize("print", &[e])
+    }
+
+    fn visit_declaration_stmt(&mut self, name: &Token, init: Option<&Expr>) -> String {
+        let mut parts = vec![name.lexeme.clone()];
+        parts.extend(init.map(|e| e.accept(self)));
+        self.parenthesize("var", &parts)
+    }
+
+    fn visit_const_stmt(&mut self, name: &Token, init: &Expr) -> String {
+        let parts = [name.lexeme.clone(), init.accept(self)];
+        self.parenthesize("const", &parts)
+    }
+
+    fn visit_if(&mut self, expr: &Expr, then_stmt: &Stmt, else_stmt: Option<&Stmt>) -> String {
+        let mut parts = vec![expr.accept(self), then_stmt.accept(self)];
+        parts.extend(else_stmt.map(|s| s.accept(self)));
+        self.parenthesize("if", &parts)
+    }
+
+    fn visit_while(&mut self, expr: &Expr, body: &Stmt) -> String {
+        let parts = [expr.accept(self), body.accept(self)];
+        self.parenthesize("while", &parts)
+    }
+
+    fn visit_break(&mut self, _line: u32) -> String {
+        self.parenthesize("break", &[])
+    }
+
+    fn visit_function(&mut self, name: &Token, signature: Rc<Signature>, body: Rc<Stmt>) -> String {
+        let parts = [format!("{}{}", name.lexeme, signature), body.accept(self)];
+        self.parenthesize("fun", &parts)
+    }
+
+    fn visit_return(&mut self, _line: u64, expr: &Expr) -> String {
+        let e = expr.accept(self);
+        self.parenthesize("return", &[e])
+    }
+}
@@ -0,0 +1,231 @@
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use crate::{env::Env, error::ResultMSG, hooks::Hooks, object::Object, source_line, stmt::Stmt};
+
+/// The name of the frame the script itself runs in.
+const SCRIPT: &str = "<script>";
+
+/// What a function cost over the whole run.
+pub struct Function {
+    pub name: String,
+    pub calls: u64,
+    /// Time from entering the function to leaving it, counted once for
+    /// recursive calls.
+    pub total: Duration,
+    /// Time spent in the function's own statements, not in its callees.
+    pub own: Duration,
+}
+
+/// What a source line cost over the whole run.
+pub struct Line {
+    pub line: u32,
+    /// Statements run on the line.
+    pub hits: u64,
+    pub time: Duration,
+}
+
+struct Frame {
+    name: String,
+    start: Instant,
+    /// Time spent in calls made from this frame.
+    children: Duration,
+    /// The line the caller was running when it made the call.
+    line: Option<u32>,
+}
+
+/// Times a program through the interpreter's hooks: calls and time per
+/// function, hits and time per line, and time per call stack.
+pub struct Profiler {
+    functions: HashMap<String, Function>,
+    lines: HashMap<u32, Line>,
+    /// Own time per call stack, keyed by the frame names joined by `;`.
+    stacks: HashMap<String, Duration>,
+    frames: Vec<Frame>,
+    /// The line running and since when.
+    line: Option<u32>,
+    since: Instant,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Profiler {
+            functions: HashMap::new(),
+            lines: HashMap::new(),
+            stacks: HashMap::new(),
+            frames: Vec::new(),
+            line: None,
+            since: Instant::now(),
+        }
+    }
+
+    /// Starts timing the script.
+    pub fn start(&mut self) {
+        self.push(SCRIPT);
+    }
+
+    /// Stops timing, closing the script's frame and any calls an error
+    /// left open.
+    pub fn finish(&mut self) {
+        while !self.frames.is_empty() {
+            self.pop();
+        }
+    }
+
+    /// The functions called, costliest first by own time.
+    pub fn functions(&self) -> Vec<&Function> {
+        let mut functions: Vec<&Function> = self.functions.values().collect();
+        functions.sort_by(|a, b| b.own.cmp(&a.own).then(a.name.cmp(&b.name)));
+        functions
+    }
+
+    /// The lines run, costliest first.
+    pub fn lines(&self) -> Vec<&Line> {
+        let mut lines: Vec<&Line> = self.lines.values().collect();
+        lines.sort_by(|a, b| b.time.cmp(&a.time).then(a.line.cmp(&b.line)));
+        lines
+    }
+
+    /// The report printed by `--profile`, quoting lines from `code`.
+    pub fn report(&self, code: &str) -> String {
+        let source: Vec<&str> = code.lines().collect();
+        let mut out = String::new();
+
+        let _ = writeln!(
+            out,
+            "{:>8} {:>12} {:>12}  function",
+            "calls", "total ms", "self ms"
+        );
+        for f in self.functions() {
+            let _ = writeln!(
+                out,
+                "{:>8} {:>12.3} {:>12.3}  {}",
+                f.calls,
+                millis(f.total),
+                millis(f.own),
+                f.name
+            );
+        }
+
+        let _ = writeln!(out, "\n{:>8} {:>12}  line", "hits", "ms");
+        for l in self.lines() {
+            let text = source_line(&source, l.line).trim();
+            let _ = writeln!(
+                out,
+                "{:>8} {:>12.3}  {:<5} {}",
+                l.hits,
+                millis(l.time),
+                l.line,
+                text
+            );
+        }
+        out
+    }
+
+    /// Own time per call stack in microseconds, one stack per line in the
+    /// folded format flame graph tools read.
+    pub fn folded(&self) -> String {
+        let mut stacks: Vec<(&String, &Duration)> = self.stacks.iter().collect();
+        stacks.sort();
+        stacks
+            .iter()
+            .map(|(stack, time)| format!("{} {}\n", stack, time.as_micros()))
+            .collect()
+    }
+
+    /// Charges the time since the last event to the running line.
+    fn charge(&mut self) -> Instant {
+        let now = Instant::now();
+        if let Some(line) = self.line {
+            let entry = self.lines.entry(line).or_insert(Line {
+                line,
+                hits: 0,
+                time: Duration::ZERO,
+            });
+            entry.time += now - self.since;
+        }
+        self.since = now;
+        now
+    }
+
+    fn push(&mut self, name: &str) {
+        let start = self.charge();
+        self.frames.push(Frame {
+            name: name.to_string(),
+            start,
+            children: Duration::ZERO,
+            line: self.line.take(),
+        });
+    }
+
+    fn pop(&mut self) {
+        let now = self.charge();
+        let frame = match self.frames.pop() {
+            Some(frame) => frame,
+            None => return,
+        };
+        let total = now - frame.start;
+        let own = total.saturating_sub(frame.children);
+
+        let mut stack: Vec<&str> = self.frames.iter().map(|f| f.name.as_str()).collect();
+        stack.push(&frame.name);
+        *self.stacks.entry(stack.join(";")).or_default() += own;
+
+        let recursive = self.frames.iter().any(|f| f.name == frame.name);
+        let entry = self
+            .functions
+            .entry(frame.name.clone())
+            .or_insert(Function {
+                name: frame.name.clone(),
+                calls: 0,
+                total: Duration::ZERO,
+                own: Duration::ZERO,
+            });
+        entry.calls += 1;
+        entry.own += own;
+        if !recursive {
+            entry.total += total;
+        }
+
+        if let Some(caller) = self.frames.last_mut() {
+            caller.children += total;
+        }
+        self.line = frame.line;
+    }
+}
+
+fn millis(time: Duration) -> f64 {
+    time.as_secs_f64() * 1000.0
+}
+
+impl Hooks for Profiler {
+    fn statement(&mut self, stmt: &Stmt, _env: &Rc<Env>) -> ResultMSG<()> {
+        let line = match stmt.line() {
+            Some(line) => line,
+            None => return Ok(()),
+        };
+        self.charge();
+        self.line = Some(line);
+        self.lines
+            .entry(line)
+            .or_insert(Line {
+                line,
+                hits: 0,
+                time: Duration::ZERO,
+            })
+            .hits += 1;
+        Ok(())
+    }
+
+    fn enter(&mut self, name: &str, _body: &Stmt, _args: &[(String, Object)]) {
+        self.push(name);
+    }
+
+    fn exit(&mut self, _name: &str, _result: &ResultMSG<Object>) {
+        self.pop();
+    }
+}
@@ -0,0 +1,185 @@
+use std::{fs, io, time::Instant};
+
+use crate::{
+    editor::Editor,
+    error::ResultMSG,
+    interpreter::Interpreter,
+    printer::AstPrinter,
+    run,
+    scanner::{self, Scanner, StmtIterator},
+};
+
+const HELP: &str = "\
+:help          show this message
+:env           list the global bindings
+:load PATH     run a file in the current session
+:reset         start over with a fresh interpreter
+:tokens CODE   show the tokens of CODE without running it
+:ast CODE      show the syntax tree of CODE without running it
+:time CODE     run CODE and report how long it took
+:quit          leave the REPL";
+
+pub struct Repl {
+    interpreter: Interpreter,
+    compat: bool,
+}
+
+impl Repl {
+    pub fn new(compat: bool) -> Self {
+        Repl {
+            interpreter: Interpreter::new(true).compat(compat),
+            compat,
+        }
+    }
+
+    pub fn run(&mut self) -> io::Result<()> {
+        let mut editor = Editor::new(Editor::history_path());
+
+        let mut entry = String::new();
+        loop {
+            let prompt = if entry.is_empty() { "> " } else { "... " };
+            let line = match editor.read_line(prompt, &|word| self.completions(word)) {
+                Ok(Some(line)) => line,
+                Ok(None) => break,
+                // Ctrl-C abandons the entry being typed
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {
+                    entry.clear();
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
+            if entry.is_empty() && line.trim_start().starts_with(':') {
+                match self.command(line.trim()) {
+                    Some(out) if out.is_empty() => {}
+                    Some(out) => println!("{}", out),
+                    None => break,
+                }
+                continue;
+            }
+
+            // An empty line submits a pending entry as is.
+            if line.trim().is_empty() {
+                if entry.is_empty() {
+                    continue;
+                }
+            } else {
+                entry.push_str(&line);
+                entry.push('\n');
+                if is_incomplete(&entry) {
+                    continue;
+                }
+            }
+
+            let _ = self.entry(&std::mem::take(&mut entry));
+        }
+        Ok(())
+    }
+
+    /// Runs one entry. Errors are already reported by `run`; the globals
+    /// go back to how they were before a failed entry, so it can be retyped.
+    pub fn entry(&mut self, code: &str) -> ResultMSG<()> {
+        let env = self.interpreter.env.clone();
+        let snapshot = env.snapshot();
+        let res = run(code, &mut self.interpreter);
+        if res.is_err() {
+            env.restore(snapshot);
+        }
+        res
+    }
+
+    /// Keywords and global names starting with `word`, for tab completion.
+    pub fn completions(&self, word: &str) -> Vec<String> {
+        let mut names = self.interpreter.env.names();
+        names.extend(scanner::keywords().into_iter().map(String::from));
+        names.retain(|n| n.starts_with(word));
+        names
+    }
+
+    /// Handles a `:command`, returning the text to show, or `None` when the
+    /// session should end.
+    pub fn command(&mut self, line: &str) -> Option<String> {
+        let (name, arg) = match line.split_once(char::is_whitespace) {
+            Some((name, arg)) => (name, arg.trim()),
+            None => (line, ""),
+        };
+
+        let out = match name {
+            ":quit" => return None,
+            ":help" => HELP.to_string(),
+            ":env" => {
+                let mut names = self.interpreter.env.names();
+                names.sort();
+                names
+                    .iter()
+                    .filter_map(|n| Some(format!("{} = {}", n, self.interpreter.env.get(n).ok()?)))
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            }
+            ":load" => match fs::read_to_string(arg) {
+                Ok(code) => {
+                    let _ = self.entry(&code);
+                    String::new()
+                }
+                Err(e) => format!("cannot read {}: {}", arg, e),
+            },
+            ":reset" => {
+                self.interpreter = Interpreter::new(true).compat(self.compat);
+                String::new()
+            }
+            ":tokens" => {
+                let mut scanner = Scanner::new(arg.to_string());
+                let tokens = scanner.scan_tokens();
+                let mut lines: Vec<String> = scanner.errors.iter().map(|e| e.to_string()).collect();
+                lines.extend(
+                    tokens
+                        .iter()
+                        .map(|t| format!("{:?} {:?}", t.token_type, t.lexeme)),
+                );
+                lines.join("\n")
+            }
+            ":ast" => {
+                let mut scanner = Scanner::new(terminated(arg));
+                scanner.scan_tokens();
+                let mut lines: Vec<String> = scanner.errors.iter().map(|e| e.to_string()).collect();
+                if lines.is_empty() {
+                    lines.extend(scanner.statements().map(|res| match res {
+                        Ok(stmt) => AstPrinter::stmt(&stmt),
+                        Err(e) => e.to_string(),
+                    }));
+                }
+                lines.join("\n")
+            }
+            ":time" => {
+                let start = Instant::now();
+                let _ = self.entry(&terminated(arg));
+                format!("took {:?}", start.elapsed())
+            }
+            _ => format!("unknown command `{}`, see :help", name),
+        };
+
+        Some(out)
+    }
+}
+
+/// Whether `code` stops in the middle of a statement, string or comment, so
+/// the REPL should keep reading lines before running it.
+pub fn is_incomplete(code: &str) -> bool {
+    let mut scanner = Scanner::new(code.to_string());
+    scanner.scan_tokens();
+    if scanner.is_incomplete() {
+        return true;
+    }
+
+    let mut parser = scanner.statements();
+    parser.by_ref().any(|res| res.is_err()) && parser.is_incomplete()
+}
+
+/// Adds the `;` that a lone expression typed after a command leaves out.
+fn terminated(code: &str) -> String {
+    let closed = format!("{};", code);
+    if is_incomplete(code) && !is_incomplete(&closed) {
+        return closed;
+    }
+    code.to_string()
+}
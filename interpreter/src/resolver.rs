@@ -0,0 +1,289 @@
+use std::{collections::HashMap, ops::Deref, rc::Rc};
+
+use crate::{
+    error::Error,
+    expr::{Expr, Visitor as ExprVisitor},
+    function::Signature,
+    stmt::{Stmt, Visitor as StmtVisitor},
+    token::Token,
+};
+
+struct Binding {
+    constant: bool,
+    /// A predefined global, which a declaration may replace.
+    predefined: bool,
+}
+
+/// Walks statements before they run, mirroring the scopes the interpreter
+/// will create, and reports errors that can be found without executing.
+pub struct Resolver {
+    scopes: Vec<HashMap<String, Binding>>,
+    pub errors: Vec<Error>,
+    functions: usize,
+    loops: usize,
+    /// Whether the global scope holds every name the program can see, so
+    /// references to anything else are errors.
+    complete: bool,
+    /// Names used in function bodies that were not declared yet; they may
+    /// still be declared later in an enclosing scope before the call.
+    unresolved: Vec<Token>,
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Resolver {
+            scopes: vec![HashMap::new()],
+            errors: Vec::new(),
+            functions: 0,
+            loops: 0,
+            complete: false,
+            unresolved: Vec::new(),
+        }
+    }
+
+    /// Declares the predefined globals and reports references to names that
+    /// are never declared. Only sound for a whole program, not a REPL entry
+    /// that may use globals from earlier entries.
+    pub fn globals(mut self, names: Vec<String>) -> Self {
+        for name in names {
+            let binding = Binding {
+                constant: false,
+                predefined: true,
+            };
+            self.scopes[0].insert(name, binding);
+        }
+        self.complete = true;
+        self
+    }
+
+    /// Stops reporting undefined names, for a program with statements left
+    /// out because they did not parse.
+    pub fn partial(mut self) -> Self {
+        self.complete = false;
+        self
+    }
+
+    pub fn resolve(&mut self, s: &Stmt) {
+        s.accept(self)
+    }
+
+    /// Reports the references in function bodies that no enclosing scope
+    /// ever declared. Call once every statement has been resolved.
+    pub fn finish(&mut self) {
+        for name in std::mem::take(&mut self.unresolved) {
+            if !self.scopes[0].contains_key(&name.lexeme) {
+                self.error(&name, format!("variable `{}` is undefined", name.lexeme));
+            }
+        }
+    }
+
+    fn error(&mut self, token: &Token, msg: String) {
+        self.errors
+            .push(Error::Resolve(token.line, msg, token.lexeme.clone()));
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        if let Some(scope) = self.scopes.pop() {
+            self.unresolved.retain(|t| !scope.contains_key(&t.lexeme));
+        }
+    }
+
+    fn declare(&mut self, name: &Token, constant: bool) {
+        let scope = self.scopes.last_mut().unwrap();
+        let binding = Binding {
+            constant,
+            predefined: false,
+        };
+        if let Some(Binding {
+            predefined: false, ..
+        }) = scope.insert(name.lexeme.clone(), binding)
+        {
+            self.error(name, format!("variable `{}` already defined", name.lexeme));
+        }
+    }
+
+    fn reference(&mut self, name: &Token) {
+        if !self.complete || self.lookup(&name.lexeme).is_some() {
+            return;
+        }
+
+        if self.functions > 0 {
+            self.unresolved.push(name.clone());
+        } else {
+            self.error(name, format!("variable `{}` is undefined", name.lexeme));
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Binding> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    fn check_assign(&mut self, name: &Token) {
+        self.reference(name);
+        if let Some(Binding { constant: true, .. }) = self.lookup(&name.lexeme) {
+            self.error(name, format!("cannot assign to constant `{}`", name.lexeme));
+        }
+    }
+}
+
+impl ExprVisitor<()> for Resolver {
+    fn visit_expr(&mut self, expr: &Expr) {
+        match *expr {
+            Expr::Identifier(ref name) => self.reference(name),
+            Expr::Literal(_) => {}
+            Expr::Grouping(ref inside) => inside.accept(self),
+            Expr::Unary(_, ref rhs) => rhs.accept(self),
+            Expr::Binary(ref lhs, _, ref rhs) => {
+                lhs.accept(self);
+                rhs.accept(self);
+            }
+            Expr::Assignment(ref id, ref rhs) => self.visit_assignment(expr, id, rhs),
+            Expr::Compound(ref id, ref op, ref rhs) => self.visit_compound(expr, id, op, rhs),
+            Expr::Update(ref id, ref op, prefix) => self.visit_update(expr, id, op, prefix),
+            Expr::Call(ref callee, _, ref args, ref named) => {
+                callee.accept(self);
+                args.iter().for_each(|a| a.accept(self));
+                named.iter().for_each(|(_, a)| a.accept(self));
+            }
+            Expr::Index(ref object, _, ref index) => {
+                object.accept(self);
+                index.accept(self);
+            }
+            Expr::Interpolation(ref parts) => parts.iter().for_each(|p| p.accept(self)),
+            Expr::Conditional(ref c, ref t, ref e) => {
+                c.accept(self);
+                t.accept(self);
+                e.accept(self);
+            }
+            Expr::Get(ref object, _, _) => object.accept(self),
+        }
+    }
+
+    fn visit_assignment(&mut self, _expr: &Expr, n: &Token, rhs: &Box<Expr>) {
+        rhs.accept(self);
+        self.check_assign(n);
+    }
+
+    fn visit_compound(&mut self, _expr: &Expr, n: &Token, _op: &Token, rhs: &Expr) {
+        rhs.accept(self);
+        self.check_assign(n);
+    }
+
+    fn visit_update(&mut self, _expr: &Expr, n: &Token, _op: &Token, _prefix: bool) {
+        self.check_assign(n);
+    }
+}
+
+impl StmtVisitor<()> for Resolver {
+    fn visit_stmt(&mut self, s: &Stmt) {
+        match *s {
+            Stmt::Empty => {}
+            Stmt::Break(l) => self.visit_break(l),
+            Stmt::Print(ref e) => self.visit_print_stmt(e),
+            Stmt::Expression(ref e) => self.visit_expression_stmt(e),
+            Stmt::Block(ref ss) => self.visit_block_stmt(ss),
+            Stmt::Declaration(ref n, ref e) => self.visit_declaration_stmt(n, e.as_ref()),
+            Stmt::Const(ref n, ref e) => self.visit_const_stmt(n, e),
+            Stmt::If(ref c, ref t, ref e) => {
+                self.visit_if(c, t.as_ref(), e.as_ref().map(|x| x.deref()))
+            }
+            Stmt::While(ref e, ref b) => self.visit_while(e, b.deref()),
+            Stmt::Return(l, ref e) => self.visit_return(l, e),
+            Stmt::Function(ref n, ref s, ref b) => self.visit_function(n, s.clone(), b.clone()),
+            Stmt::Doc(_, ref s) => self.visit_stmt(s),
+        }
+    }
+
+    fn visit_block_stmt(&mut self, statements: &Vec<Stmt>) {
+        self.begin_scope();
+        for stmt in statements {
+            stmt.accept(self);
+        }
+        self.end_scope();
+    }
+
+    fn visit_expression_stmt(&mut self, expression: &Expr) {
+        expression.accept(self)
+    }
+
+    fn visit_print_stmt(&mut self, expression: &Expr) {
+        expression.accept(self)
+    }
+
+    fn visit_declaration_stmt(&mut self, name: &Token, init: Option<&Expr>) {
+        if let Some(init) = init {
+            init.accept(self);
+        }
+        self.declare(name, false);
+    }
+
+    fn visit_const_stmt(&mut self, name: &Token, init: &Expr) {
+        init.accept(self);
+        self.declare(name, true);
+    }
+
+    fn visit_if(&mut self, expr: &Expr, then_stmt: &Stmt, else_stmt: Option<&Stmt>) {
+        expr.accept(self);
+        then_stmt.accept(self);
+        if let Some(else_stmt) = else_stmt {
+            else_stmt.accept(self);
+        }
+    }
+
+    fn visit_while(&mut self, expr: &Expr, body: &Stmt) {
+        expr.accept(self);
+        self.loops += 1;
+        body.accept(self);
+        self.loops -= 1;
+    }
+
+    fn visit_break(&mut self, line: u32) {
+        if self.loops == 0 {
+            self.errors.push(Error::Resolve(
+                line,
+                "`break` outside of a loop".to_string(),
+                "break".to_string(),
+            ));
+        }
+    }
+
+    fn visit_function(&mut self, name: &Token, signature: Rc<Signature>, body: Rc<Stmt>) {
+        self.declare(name, false);
+
+        // a loop around the declaration does not extend into the body
+        let loops = std::mem::replace(&mut self.loops, 0);
+        self.functions += 1;
+        self.begin_scope();
+        for param in &signature.params {
+            if let Some(ref default) = param.default {
+                default.accept(self);
+            }
+            self.scopes.last_mut().unwrap().insert(
+                param.name.clone(),
+                Binding {
+                    constant: false,
+                    predefined: false,
+                },
+            );
+        }
+        body.accept(self);
+        self.end_scope();
+        self.functions -= 1;
+        self.loops = loops;
+    }
+
+    fn visit_return(&mut self, line: u64, expr: &Expr) {
+        if self.functions == 0 {
+            self.errors.push(Error::Resolve(
+                line as u32,
+                "`return` outside of a function".to_string(),
+                "return".to_string(),
+            ));
+        }
+        expr.accept(self)
+    }
+}
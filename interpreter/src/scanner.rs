@@ -1,19 +1,31 @@
 use std::collections::HashMap;
 
 use crate::{
+    error::Error,
     parser::Parser,
-    token::{Literal::*, Token, TokenType, TokenType::*},
+    token::{Literal, Token, TokenType, TokenType::*},
 };
 
 pub struct Scanner {
-    source: String,
+    source: Vec<char>,
     pub tokens: Vec<Token>,
+    pub errors: Vec<Error>,
+    interpolations: Vec<Interpolation>,
+    incomplete: bool,
+    lossless: bool,
     start: usize,
     current: usize,
     line: u32,
     keywords: HashMap<&'static str, TokenType>,
 }
 
+/// An open `${` inside a string, with the brace depth of the embedded
+/// expression and whether the enclosing string is triple-quoted.
+struct Interpolation {
+    depth: usize,
+    triple: bool,
+}
+
 fn fill_keywords(keywords: &mut HashMap<&str, TokenType>) {
     keywords.insert("and", AND);
     keywords.insert("class", CLASS);
@@ -30,12 +42,21 @@ fn fill_keywords(keywords: &mut HashMap<&str, TokenType>) {
     keywords.insert("this", THIS);
     keywords.insert("true", TRUE);
     keywords.insert("var", VAR);
+    keywords.insert("let", LET);
+    keywords.insert("const", CONST);
     keywords.insert("while", WHILE);
     keywords.insert("break", BREAK);
     keywords.insert("fun", FUN);
     keywords.insert("return", RETURN);
 }
 
+/// The reserved words, for tools such as completion.
+pub fn keywords() -> Vec<&'static str> {
+    let mut keywords = HashMap::new();
+    fill_keywords(&mut keywords);
+    keywords.into_keys().collect()
+}
+
 impl Scanner {
     pub fn new(source: String) -> Self {
         let mut keywords = HashMap::new();
@@ -43,8 +64,12 @@ impl Scanner {
         fill_keywords(&mut keywords);
 
         Scanner {
-            source,
+            source: source.chars().collect(),
             tokens: Vec::new(),
+            errors: Vec::new(),
+            interpolations: Vec::new(),
+            incomplete: false,
+            lossless: false,
             start: 0,
             current: 0,
             line: 1,
@@ -52,21 +77,59 @@ impl Scanner {
         }
     }
 
+    /// Keeps comments as `COMMENT` tokens and gives every token its exact
+    /// source text as lexeme, for tools that print the source back out.
+    pub fn lossless(mut self, lossless: bool) -> Self {
+        self.lossless = lossless;
+        self
+    }
+
     pub fn scan_tokens(&mut self) -> Vec<Token> {
         while !self.at_end() {
             self.start = self.current;
+            let count = self.tokens.len();
             self.scan_token();
+
+            let column = self.column(self.start);
+            for token in &mut self.tokens[count..] {
+                token.column = column;
+            }
+            if self.lossless {
+                if let Some(token) = self.tokens.get_mut(count) {
+                    token.lexeme = self.source[self.start..self.current].iter().collect();
+                }
+            }
+        }
+        if !self.interpolations.is_empty() {
+            self.interpolations.clear();
+            self.incomplete = true;
+            self.error("unterminated string interpolation", "${".to_string());
         }
         self.tokens.push(Token {
             token_type: TokenType::EOF,
             lexeme: "".to_string(),
-            literal: None,
+            literal: Literal::None,
             line: self.line,
+            column: self.column(self.current),
         });
 
         self.tokens.clone()
     }
 
+    /// Whether scanning stopped inside an unterminated string, comment or
+    /// interpolation, so more input could still complete it.
+    pub fn is_incomplete(&self) -> bool {
+        self.incomplete
+    }
+
+    fn column(&self, offset: usize) -> u32 {
+        self.source[..offset]
+            .iter()
+            .rev()
+            .take_while(|c| **c != '\n')
+            .count() as u32
+    }
+
     fn at_end(&self) -> bool {
         self.current >= self.source.len()
     }
@@ -77,14 +140,78 @@ impl Scanner {
         match c {
             '(' => self.add_token(LEFT_PAREN),
             ')' => self.add_token(RIGHT_PAREN),
-            '{' => self.add_token(LEFT_BRACE),
-            '}' => self.add_token(RIGHT_BRACE),
+            '{' => {
+                if let Some(i) = self.interpolations.last_mut() {
+                    i.depth += 1;
+                }
+                self.add_token(LEFT_BRACE)
+            }
+            '}' => match self.interpolations.last_mut() {
+                Some(i) if i.depth == 0 => {
+                    let triple = i.triple;
+                    self.interpolations.pop();
+                    self.string_body(false, triple, self.line);
+                }
+                Some(i) => {
+                    i.depth -= 1;
+                    self.add_token(RIGHT_BRACE)
+                }
+                _ => self.add_token(RIGHT_BRACE),
+            },
             ',' => self.add_token(COMMA),
-            '.' => self.add_token(DOT),
-            '-' => self.add_token(MINUS),
-            '+' => self.add_token(PLUS),
+            '.' => {
+                if self.peek() == '.' && self.peek_next() == '.' {
+                    self.current += 2;
+                    self.add_token(ELLIPSIS);
+                } else {
+                    self.add_token(DOT);
+                }
+            }
+            '[' => self.add_token(LEFT_BRACKET),
+            ']' => self.add_token(RIGHT_BRACKET),
+            '-' => {
+                if self.match_tok('-') {
+                    self.add_token(MINUS_MINUS);
+                } else {
+                    self.add_token_assign(MINUS, MINUS_EQUAL);
+                }
+            }
+            '+' => {
+                if self.match_tok('+') {
+                    self.add_token(PLUS_PLUS);
+                } else {
+                    self.add_token_assign(PLUS, PLUS_EQUAL);
+                }
+            }
             ';' => self.add_token(SEMICOLON),
-            '*' => self.add_token(STAR),
+            ':' => self.add_token(COLON),
+            '?' => {
+                if self.match_tok('?') {
+                    self.add_token(QUESTION_QUESTION);
+                } else if self.match_tok('.') {
+                    self.add_token(QUESTION_DOT);
+                } else {
+                    self.add_token(QUESTION);
+                }
+            }
+            '*' => {
+                if self.match_tok('*') {
+                    self.add_token_assign(STAR_STAR, STAR_STAR_EQUAL);
+                } else {
+                    self.add_token_assign(STAR, STAR_EQUAL);
+                }
+            }
+            '%' => self.add_token_assign(PERCENT, PERCENT_EQUAL),
+            '&' => self.add_token_assign(AMPERSAND, AMPERSAND_EQUAL),
+            '|' => self.add_token_assign(PIPE, PIPE_EQUAL),
+            '^' => self.add_token_assign(CARET, CARET_EQUAL),
+            '~' => {
+                if self.match_tok('/') {
+                    self.add_token_assign(TILDE_SLASH, TILDE_SLASH_EQUAL);
+                } else {
+                    self.add_token(TILDE);
+                }
+            }
             '!' => {
                 let tok = self.match_tok('=');
                 if tok {
@@ -102,61 +229,119 @@ impl Scanner {
                 }
             }
             '<' => {
-                let tok = self.match_tok('=');
-                if tok {
+                if self.match_tok('=') {
                     self.add_token(LESS_EQUAL);
+                } else if self.match_tok('<') {
+                    self.add_token_assign(LESS_LESS, LESS_LESS_EQUAL);
                 } else {
                     self.add_token(LESS);
                 }
             }
             '>' => {
-                let tok = self.match_tok('=');
-                if tok {
+                if self.match_tok('=') {
                     self.add_token(GREATER_EQUAL);
+                } else if self.match_tok('>') {
+                    self.add_token_assign(GREATER_GREATER, GREATER_GREATER_EQUAL);
                 } else {
                     self.add_token(GREATER);
                 }
             }
             '/' => {
                 if self.match_tok('/') {
-                    while self.peek() != '\n' && !self.at_end() {
-                        self.advance();
+                    if self.peek() == '/' && self.peek_next() != '/' {
+                        self.doc_comment();
+                    } else {
+                        while self.peek() != '\n' && !self.at_end() {
+                            self.advance();
+                        }
+                        self.add_comment(self.line);
                     }
+                } else if self.match_tok('*') {
+                    let line = self.line;
+                    self.block_comment();
+                    self.add_comment(line);
                 } else {
-                    self.add_token(SLASH);
+                    self.add_token_assign(SLASH, SLASH_EQUAL);
                 }
             }
             '\t' | '\r' | ' ' => {}
             '\n' => {
                 self.line += 1;
             }
-            '"' => self.string(),
+            '"' => self.string(false),
+            'r' if self.peek() == '"' => {
+                self.advance();
+                self.string(true)
+            }
             '0'..='9' => self.number(),
-            'a'..='z' | 'A'..='Z' => self.identifier(),
-            _ => panic!("invalid token recieved '{}'", c),
+            'a'..='z' | 'A'..='Z' | '_' => self.identifier(),
+            _ => self.error("invalid token recieved", c.to_string()),
         };
     }
 
     fn advance(&mut self) -> char {
         self.current += 1;
-        self.source.chars().nth(self.current - 1).unwrap()
+        self.source[self.current - 1]
+    }
+
+    fn error(&mut self, msg: &str, near: String) {
+        self.errors
+            .push(Error::Lexer(self.line, msg.to_string(), near));
+    }
+
+    fn text(&self, start: usize, end: usize) -> String {
+        self.source[start..end].iter().collect()
     }
 
     fn add_token(&mut self, token: TokenType) {
         self.tokens.push(Token {
             token_type: token,
-            lexeme: format!("{:?}", token),
-            literal: None,
+            lexeme: self.text(self.start, self.current),
+            literal: Literal::None,
+            line: self.line,
+            column: 0,
+        });
+    }
+
+    fn add_comment(&mut self, line: u32) {
+        if self.lossless {
+            self.tokens.push(Token {
+                token_type: COMMENT,
+                lexeme: String::new(),
+                literal: Literal::None,
+                line,
+                column: 0,
+            });
+        }
+    }
+
+    /// Adds `assign` when the operator is directly followed by `=`, as in
+    /// `+=`, otherwise the plain operator.
+    fn add_token_assign(&mut self, op: TokenType, assign: TokenType) {
+        if self.match_tok('=') {
+            self.add_token(assign);
+        } else {
+            self.add_token(op);
+        }
+    }
+
+    fn add_token_literal(&mut self, token: TokenType, literal: Literal) {
+        self.tokens.push(Token {
+            token_type: token,
+            lexeme: self.text(self.start, self.current),
+            literal,
             line: self.line,
+            column: 0,
         });
     }
 
-    fn add_token_str(&mut self, text: String) {
+    fn add_token_str(&mut self, text: String, line: u32) {
         self.tokens.push(Token {
             token_type: STRING,
             lexeme: text.clone(),
-            literal: StringLit(text),
-            line: self.line,
+            literal: Literal::StringLit(text),
+            line,
+            column: 0,
         });
     }
 
@@ -164,8 +349,9 @@ impl Scanner {
         self.tokens.push(Token {
             token_type: NUMBER,
             lexeme: n.to_string(),
-            literal: Number(n),
+            literal: Literal::Number(n),
             line: self.line,
+            column: 0,
         });
     }
 
@@ -173,7 +359,7 @@ impl Scanner {
         if self.at_end() {
             return false;
         }
-        if self.source.chars().nth(self.current).unwrap() != c {
+        if self.source[self.current] != c {
             return false;
         }
         self.current += 1;
@@ -184,63 +370,303 @@ impl Scanner {
         if self.at_end() {
             return '\0';
         } else {
-            return self.source.chars().nth(self.current).unwrap();
+            return self.source[self.current];
         }
     }
 
     fn peek_next(&self) -> char {
-        if self.current + 1 >= self.source.len() {
+        self.peek_at(1)
+    }
+
+    fn peek_at(&self, offset: usize) -> char {
+        if self.current + offset >= self.source.len() {
             return '\0';
         }
-        return self.source.chars().nth(self.current + 1).unwrap();
+        return self.source[self.current + offset];
     }
 
-    fn string(&mut self) {
-        while self.peek() != '"' && !self.at_end() {
-            if self.peek() == '\n' {
-                self.line += 1;
+    fn string(&mut self, raw: bool) {
+        let line = self.line;
+        let triple = self.peek() == '"' && self.peek_next() == '"';
+        if triple {
+            self.current += 2;
+        }
+
+        self.string_body(raw, triple, line);
+    }
+
+    fn string_body(&mut self, raw: bool, triple: bool, line: u32) {
+        let mut value = String::new();
+
+        loop {
+            if self.at_end() {
+                self.incomplete = true;
+                let near = self.text(self.start, (self.start + 10).min(self.current));
+                self.errors
+                    .push(Error::Lexer(line, "unterminated string".to_string(), near));
+                return;
+            }
+
+            if triple && self.peek() == '"' && self.peek_next() == '"' && self.peek_at(2) == '"' {
+                self.current += 3;
+                break;
+            } else if !triple && self.peek() == '"' {
+                self.advance();
+                break;
+            }
+
+            if !raw && self.peek() == '$' && self.peek_next() == '{' {
+                self.current += 2;
+                self.tokens.push(Token {
+                    token_type: INTERPOLATION,
+                    lexeme: value.clone(),
+                    literal: Literal::StringLit(value),
+                    line,
+                    column: 0,
+                });
+                self.interpolations.push(Interpolation { depth: 0, triple });
+                return;
+            }
+
+            match self.advance() {
+                '\\' if !raw => {
+                    if let Some(c) = self.escape() {
+                        value.push(c);
+                    }
+                }
+                c => {
+                    if c == '\n' {
+                        self.line += 1;
+                    }
+                    value.push(c);
+                }
             }
-            self.advance();
         }
 
+        self.add_token_str(value, line);
+    }
+
+    fn escape(&mut self) -> Option<char> {
         if self.at_end() {
-            panic!("Unterminated string at line {}", self.line);
+            return None;
         }
 
-        self.advance();
+        match self.advance() {
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            'r' => Some('\r'),
+            '0' => Some('\0'),
+            '\\' => Some('\\'),
+            '"' => Some('"'),
+            '$' => Some('$'),
+            'u' => self.unicode_escape(),
+            c => {
+                if c == '\n' {
+                    self.line += 1;
+                }
+                self.error("invalid escape sequence", format!("\\{}", c));
+                None
+            }
+        }
+    }
+
+    fn unicode_escape(&mut self) -> Option<char> {
+        if !self.match_tok('{') {
+            self.error("expected '{' after \\u", "\\u".to_string());
+            return None;
+        }
+
+        let start = self.current;
+        while self.peek().is_ascii_hexdigit() {
+            self.advance();
+        }
+        let digits = self.text(start, self.current);
+
+        if !self.match_tok('}') || digits.is_empty() || digits.len() > 6 {
+            self.error("invalid unicode escape", format!("\\u{{{}", digits));
+            return None;
+        }
 
-        let s = &self.source[self.start + 1..self.current - 1];
-        self.add_token_str(s.to_string());
+        match u32::from_str_radix(&digits, 16)
+            .ok()
+            .and_then(char::from_u32)
+        {
+            Some(c) => Some(c),
+            None => {
+                self.error("invalid unicode code point", format!("\\u{{{}}}", digits));
+                None
+            }
+        }
     }
 
     fn number(&mut self) {
-        while ('0'..='9').contains(&self.peek()) {
+        if self.source[self.start] == '0' && "xXbBoO".contains(self.peek()) {
+            let radix = match self.advance() {
+                'x' | 'X' => 16,
+                'b' | 'B' => 2,
+                _ => 8,
+            };
+
+            let digits = self.digits(radix);
+            if self.invalid_suffix() {
+                return;
+            }
+
+            match digits {
+                Some(ref d) if d.is_empty() => {
+                    let near = self.text(self.start, self.current);
+                    self.error("expected digits after radix prefix", near);
+                }
+                Some(d) => match u64::from_str_radix(&d, radix) {
+                    Ok(n) => self.add_token_num(n as f64),
+                    Err(_) => {
+                        let near = self.text(self.start, self.current);
+                        self.error("number literal is too large", near);
+                    }
+                },
+                None => {}
+            }
+            return;
+        }
+
+        let mut number = self.source[self.start].to_string();
+        let mut valid = true;
+
+        match self.digits(10) {
+            Some(d) => number.push_str(&d),
+            None => valid = false,
+        }
+
+        if self.peek() == '.' && self.peek_next().is_ascii_digit() {
             self.advance();
+            match self.digits(10) {
+                Some(d) => number = format!("{}.{}", number, d),
+                None => valid = false,
+            }
         }
-        if self.peek() == '.' && ('0'..='9').contains(&self.peek_next()) {
+
+        if self.peek() == 'e' || self.peek() == 'E' {
             self.advance();
-            while ('0'..='9').contains(&self.peek()) {
-                self.advance();
+            number.push('e');
+            if self.peek() == '+' || self.peek() == '-' {
+                number.push(self.advance());
             }
+
+            match self.digits(10) {
+                Some(ref d) if d.is_empty() => {
+                    let near = self.text(self.start, self.current);
+                    self.error("expected digits in exponent", near);
+                    valid = false;
+                }
+                Some(d) => number.push_str(&d),
+                None => valid = false,
+            }
+        }
+
+        if self.invalid_suffix() || !valid {
+            return;
+        }
+
+        self.add_token_num(number.parse().unwrap());
+    }
+
+    /// Consumes a run of digits in `radix` with optional `_` separators and
+    /// returns them without separators. Separators may only sit between
+    /// digits; misplaced ones are reported and yield `None`.
+    fn digits(&mut self, radix: u32) -> Option<String> {
+        let start = self.current;
+        while self.peek().is_digit(radix) || self.peek() == '_' {
+            self.advance();
         }
 
-        let f = &self.source[self.start..self.current];
+        let raw = self.text(start, self.current);
+        let leading = start > self.start && !self.source[start - 1].is_digit(radix);
+        if raw.ends_with('_') || raw.contains("__") || (leading && raw.starts_with('_')) {
+            let near = self.text(self.start, self.current);
+            self.error("invalid digit separator in number literal", near);
+            return None;
+        }
 
-        let f: f64 = f.parse().unwrap();
+        Some(raw.replace('_', ""))
+    }
 
-        self.add_token_num(f);
+    /// Reports and skips letters or digits glued to the end of a number, as
+    /// in `0b102` or `12abc`.
+    fn invalid_suffix(&mut self) -> bool {
+        if !self.peek().is_alphanumeric() {
+            return false;
+        }
+
+        while self.peek().is_alphanumeric() || self.peek() == '_' {
+            self.advance();
+        }
+        let near = self.text(self.start, self.current);
+        self.error("invalid digit in number literal", near);
+        true
+    }
+
+    fn doc_comment(&mut self) {
+        self.advance();
+        while self.peek() != '\n' && !self.at_end() {
+            self.advance();
+        }
+
+        let text = self.text(self.start + 3, self.current);
+        let text = text.strip_prefix(' ').unwrap_or(&text).to_string();
+
+        self.tokens.push(Token {
+            token_type: DOC_COMMENT,
+            lexeme: text.clone(),
+            literal: Literal::StringLit(text),
+            line: self.line,
+            column: 0,
+        });
+    }
+
+    fn block_comment(&mut self) {
+        let line = self.line;
+        let mut depth = 1;
+
+        while depth > 0 {
+            if self.at_end() {
+                self.incomplete = true;
+                let near = self.text(self.start, (self.start + 10).min(self.current));
+                self.errors
+                    .push(Error::Lexer(line, "unterminated comment".to_string(), near));
+                return;
+            }
+
+            match (self.peek(), self.peek_next()) {
+                ('/', '*') => {
+                    depth += 1;
+                    self.current += 2;
+                }
+                ('*', '/') => {
+                    depth -= 1;
+                    self.current += 2;
+                }
+                (c, _) => {
+                    if c == '\n' {
+                        self.line += 1;
+                    }
+                    self.advance();
+                }
+            }
+        }
     }
 
     fn identifier(&mut self) {
-        while self.peek().is_alphanumeric() {
+        while self.peek().is_alphanumeric() || self.peek() == '_' {
             self.advance();
         }
-        let text = &self.source[self.start..self.current];
-        let token_type = self.keywords.get(text);
+        let text = self.text(self.start, self.current);
+        let token_type = self.keywords.get(text.as_str());
 
         match token_type {
+            Some(&TRUE) => self.add_token_literal(TRUE, Literal::True),
+            Some(&FALSE) => self.add_token_literal(FALSE, Literal::False),
             Some(k) => self.add_token(k.clone()),
-            _ => self.add_token_identifier(text.to_string()),
+            _ => self.add_token_identifier(text),
         }
     }
 
@@ -248,8 +674,9 @@ impl Scanner {
         self.tokens.push(Token {
             token_type: TokenType::IDENTIFIER,
             lexeme: id,
-            literal: None,
+            literal: Literal::None,
             line: self.line,
+            column: 0,
         });
     }
 }
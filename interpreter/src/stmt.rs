@@ -1,21 +1,56 @@
 use std::rc::Rc;
 
-use crate::expr::{Boxed, Expr};
+use crate::{
+    expr::{Boxed, Expr},
+    function::Signature,
+    token::Token,
+};
 
 pub enum Stmt {
     Empty,
     Break(u32),
     Expression(Expr),
     Print(Expr),
-    Declaration(String, Option<Expr>),
+    Declaration(Token, Option<Expr>),
+    Const(Token, Expr),
     Block(Vec<Stmt>),
     If(Expr, Box<Stmt>, Option<Box<Stmt>>),
     While(Expr, Box<Stmt>),
-    Function(String, Vec<String>, Rc<Stmt>),
+    Function(Token, Rc<Signature>, Rc<Stmt>),
     Return(u64, Expr),
+    Doc(Vec<Token>, Box<Stmt>),
 }
 
 impl Stmt {
+    /// The text of the `///` comments attached to a declaration, one line per
+    /// comment.
+    pub fn doc_comment(&self) -> Option<String> {
+        match self {
+            Stmt::Doc(docs, _) => Some(
+                docs.iter()
+                    .map(|t| t.lexeme.as_str())
+                    .collect::<Vec<&str>>()
+                    .join("\n"),
+            ),
+            _ => None,
+        }
+    }
+
+    /// The line where the statement starts, if it has any code.
+    pub fn line(&self) -> Option<u32> {
+        match self {
+            Stmt::Empty => None,
+            Stmt::Break(l) => Some(*l),
+            Stmt::Return(l, _) => Some(*l as u32),
+            Stmt::Expression(e) | Stmt::Print(e) | Stmt::If(e, _, _) | Stmt::While(e, _) => {
+                Some(e.line())
+            }
+            Stmt::Declaration(t, _) | Stmt::Const(t, _) | Stmt::Function(t, _, _) => Some(t.line),
+            Stmt::Block(ss) => ss.iter().find_map(|s| s.line()),
+            Stmt::Doc(_, s) => s.line(),
+        }
+    }
+
     pub fn accept<T>(&self, v: &mut dyn Visitor<T>) -> T {
         v.visit_stmt(self)
     }
@@ -30,7 +65,9 @@ pub trait Visitor<T> {
 
     fn visit_print_stmt(&mut self, expression: &Expr) -> T;
 
-    fn visit_declaration_stmt(&mut self, name: &String, init: Option<&Expr>) -> T;
+    fn visit_declaration_stmt(&mut self, name: &Token, init: Option<&Expr>) -> T;
+
+    fn visit_const_stmt(&mut self, name: &Token, init: &Expr) -> T;
 
     fn visit_if(&mut self, expr: &Expr, then_stmt: &Stmt, else_stmt: Option<&Stmt>) -> T;
 
@@ -38,7 +75,7 @@ pub trait Visitor<T> {
 
     fn visit_break(&mut self, line: u32) -> T;
 
-    fn visit_function(&mut self, name: String, params: &[String], body: Rc<Stmt>) -> T;
+    fn visit_function(&mut self, name: &Token, signature: Rc<Signature>, body: Rc<Stmt>) -> T;
 
     fn visit_return(&mut self, line: u64, expr: &Expr) -> T;
 }
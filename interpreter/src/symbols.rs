@@ -0,0 +1,310 @@
+use std::{collections::HashMap, ops::Deref, rc::Rc};
+
+use crate::{
+    expr::{Expr, Visitor as ExprVisitor},
+    function::Signature,
+    stmt::{Stmt, Visitor as StmtVisitor},
+    token::Token,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Variable,
+    Constant,
+    Function,
+    Parameter,
+}
+
+/// A declared name.
+pub struct Symbol {
+    pub name: String,
+    pub kind: Kind,
+    pub line: u32,
+    pub column: u32,
+    /// How the declaration reads, as in `fun add(a, b?)`.
+    pub detail: String,
+    pub doc: Option<String>,
+    /// The function the symbol is declared in, by index into the symbols.
+    pub parent: Option<usize>,
+}
+
+/// A place where a symbol's name appears in the source.
+pub struct Occurrence {
+    pub line: u32,
+    pub column: u32,
+    pub len: u32,
+    pub symbol: usize,
+    pub declaration: bool,
+}
+
+/// Which declaration every name in a program refers to, following the same
+/// scopes as the resolver.
+pub struct Index {
+    pub symbols: Vec<Symbol>,
+    pub occurrences: Vec<Occurrence>,
+}
+
+impl Index {
+    pub fn new(stmts: &[Stmt]) -> Self {
+        let mut indexer = Indexer {
+            index: Index {
+                symbols: Vec::new(),
+                occurrences: Vec::new(),
+            },
+            scopes: vec![(0, HashMap::new())],
+            next_scope: 1,
+            functions: Vec::new(),
+            doc: None,
+            deferred: Vec::new(),
+        };
+
+        for stmt in stmts {
+            stmt.accept(&mut indexer);
+        }
+        indexer.end_scope();
+        indexer.index
+    }
+
+    /// The symbol whose name covers the position, counting lines from one
+    /// like tokens do.
+    pub fn at(&self, line: u32, column: u32) -> Option<usize> {
+        self.occurrences
+            .iter()
+            .find(|o| o.line == line && o.column <= column && column <= o.column + o.len)
+            .map(|o| o.symbol)
+    }
+
+    pub fn occurrences_of(&self, symbol: usize) -> impl Iterator<Item = &Occurrence> {
+        self.occurrences.iter().filter(move |o| o.symbol == symbol)
+    }
+}
+
+/// A name used in a function body before any enclosing scope declared it,
+/// with the scopes around it that may still do so.
+struct Deferred {
+    token: Token,
+    scopes: Vec<usize>,
+}
+
+struct Indexer {
+    index: Index,
+    /// Open scopes with their ids.
+    scopes: Vec<(usize, HashMap<String, usize>)>,
+    next_scope: usize,
+    /// Symbols of the functions being walked, innermost last.
+    functions: Vec<usize>,
+    /// Doc comments waiting for the declaration they belong to.
+    doc: Option<String>,
+    deferred: Vec<Deferred>,
+}
+
+impl Indexer {
+    fn begin_scope(&mut self) {
+        self.scopes.push((self.next_scope, HashMap::new()));
+        self.next_scope += 1;
+    }
+
+    fn end_scope(&mut self) {
+        let (id, scope) = match self.scopes.pop() {
+            Some(scope) => scope,
+            None => return,
+        };
+
+        for deferred in std::mem::take(&mut self.deferred) {
+            match scope.get(&deferred.token.lexeme) {
+                Some(&symbol) if deferred.scopes.contains(&id) => {
+                    self.occurrence(&deferred.token, symbol)
+                }
+                _ => self.deferred.push(deferred),
+            }
+        }
+    }
+
+    fn declare(&mut self, name: &str, line: u32, column: u32, kind: Kind, detail: String) {
+        let symbol = self.index.symbols.len();
+        self.index.symbols.push(Symbol {
+            name: name.to_string(),
+            kind,
+            line,
+            column,
+            detail,
+            doc: self.doc.take(),
+            parent: self.functions.last().copied(),
+        });
+        self.index.occurrences.push(Occurrence {
+            line,
+            column,
+            len: name.chars().count() as u32,
+            symbol,
+            declaration: true,
+        });
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .1
+            .insert(name.to_string(), symbol);
+    }
+
+    fn reference(&mut self, name: &Token) {
+        let found = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|(_, scope)| scope.get(&name.lexeme).copied());
+
+        match found {
+            Some(symbol) => self.occurrence(name, symbol),
+            // functions may use names declared after them
+            None if !self.functions.is_empty() => self.deferred.push(Deferred {
+                token: name.clone(),
+                scopes: self.scopes.iter().map(|(id, _)| *id).collect(),
+            }),
+            None => {}
+        }
+    }
+
+    fn occurrence(&mut self, name: &Token, symbol: usize) {
+        self.index.occurrences.push(Occurrence {
+            line: name.line,
+            column: name.column,
+            len: name.lexeme.chars().count() as u32,
+            symbol,
+            declaration: false,
+        });
+    }
+}
+
+impl ExprVisitor<()> for Indexer {
+    fn visit_expr(&mut self, expr: &Expr) {
+        match *expr {
+            Expr::Identifier(ref name) => self.reference(name),
+            Expr::Literal(_) => {}
+            Expr::Grouping(ref inside) => inside.accept(self),
+            Expr::Unary(_, ref rhs) => rhs.accept(self),
+            Expr::Binary(ref lhs, _, ref rhs) => {
+                lhs.accept(self);
+                rhs.accept(self);
+            }
+            Expr::Assignment(ref id, ref rhs) | Expr::Compound(ref id, _, ref rhs) => {
+                rhs.accept(self);
+                self.reference(id);
+            }
+            Expr::Update(ref id, _, _) => self.reference(id),
+            Expr::Call(ref callee, _, ref args, ref named) => {
+                callee.accept(self);
+                args.iter().for_each(|a| a.accept(self));
+                named.iter().for_each(|(_, a)| a.accept(self));
+            }
+            Expr::Index(ref object, _, ref index) => {
+                object.accept(self);
+                index.accept(self);
+            }
+            Expr::Interpolation(ref parts) => parts.iter().for_each(|p| p.accept(self)),
+            Expr::Conditional(ref c, ref t, ref e) => {
+                c.accept(self);
+                t.accept(self);
+                e.accept(self);
+            }
+            Expr::Get(ref object, _, _) => object.accept(self),
+        }
+    }
+}
+
+impl StmtVisitor<()> for Indexer {
+    fn visit_stmt(&mut self, s: &Stmt) {
+        match *s {
+            Stmt::Empty => {}
+            Stmt::Break(l) => self.visit_break(l),
+            Stmt::Print(ref e) => self.visit_print_stmt(e),
+            Stmt::Expression(ref e) => self.visit_expression_stmt(e),
+            Stmt::Block(ref ss) => self.visit_block_stmt(ss),
+            Stmt::Declaration(ref n, ref e) => self.visit_declaration_stmt(n, e.as_ref()),
+            Stmt::Const(ref n, ref e) => self.visit_const_stmt(n, e),
+            Stmt::If(ref c, ref t, ref e) => {
+                self.visit_if(c, t.as_ref(), e.as_ref().map(|x| x.deref()))
+            }
+            Stmt::While(ref e, ref b) => self.visit_while(e, b.deref()),
+            Stmt::Return(l, ref e) => self.visit_return(l, e),
+            Stmt::Function(ref n, ref s, ref b) => self.visit_function(n, s.clone(), b.clone()),
+            Stmt::Doc(_, ref inner) => {
+                self.doc = s.doc_comment();
+                self.visit_stmt(inner);
+                self.doc = None;
+            }
+        }
+    }
+
+    fn visit_block_stmt(&mut self, statements: &Vec<Stmt>) {
+        self.begin_scope();
+        for stmt in statements {
+            stmt.accept(self);
+        }
+        self.end_scope();
+    }
+
+    fn visit_expression_stmt(&mut self, expression: &Expr) {
+        expression.accept(self)
+    }
+
+    fn visit_print_stmt(&mut self, expression: &Expr) {
+        expression.accept(self)
+    }
+
+    fn visit_declaration_stmt(&mut self, name: &Token, init: Option<&Expr>) {
+        if let Some(init) = init {
+            init.accept(self);
+        }
+        let detail = format!("var {}", name.lexeme);
+        self.declare(&name.lexeme, name.line, name.column, Kind::Variable, detail);
+    }
+
+    fn visit_const_stmt(&mut self, name: &Token, init: &Expr) {
+        init.accept(self);
+        let detail = format!("const {}", name.lexeme);
+        self.declare(&name.lexeme, name.line, name.column, Kind::Constant, detail);
+    }
+
+    fn visit_if(&mut self, expr: &Expr, then_stmt: &Stmt, else_stmt: Option<&Stmt>) {
+        expr.accept(self);
+        then_stmt.accept(self);
+        if let Some(else_stmt) = else_stmt {
+            else_stmt.accept(self);
+        }
+    }
+
+    fn visit_while(&mut self, expr: &Expr, body: &Stmt) {
+        expr.accept(self);
+        body.accept(self);
+    }
+
+    fn visit_break(&mut self, _line: u32) {}
+
+    fn visit_function(&mut self, name: &Token, signature: Rc<Signature>, body: Rc<Stmt>) {
+        let detail = format!("fun {}{}", name.lexeme, signature);
+        self.declare(&name.lexeme, name.line, name.column, Kind::Function, detail);
+        self.functions.push(self.index.symbols.len() - 1);
+
+        self.begin_scope();
+        for param in &signature.params {
+            if let Some(ref default) = param.default {
+                default.accept(self);
+            }
+            let detail = format!("parameter {}", param.name);
+            self.declare(
+                &param.name,
+                param.line,
+                param.column,
+                Kind::Parameter,
+                detail,
+            );
+        }
+        body.accept(self);
+        self.end_scope();
+
+        self.functions.pop();
+    }
+
+    fn visit_return(&mut self, _line: u64, expr: &Expr) {
+        expr.accept(self)
+    }
+}
@@ -0,0 +1,257 @@
+use std::{
+    cell::RefCell,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    analyze,
+    error::{Error, ResultMSG},
+    function::{self, Callable, NativeFunction},
+    hooks::Hooks,
+    interpreter::Interpreter,
+    object::Object,
+    read_source,
+    resolver::Resolver,
+    script_interpreter, source_line,
+    stmt::Stmt,
+    Options, EX_DATAERR, EX_IOERR,
+};
+
+/// Test functions are the top-level functions named with this prefix.
+const PREFIX: &str = "test_";
+
+/// Test files in a directory are the ones named with this suffix.
+const SUFFIX: &str = "_test.lox";
+
+/// The natives defined for tests only.
+pub const ASSERTS: [&str; 3] = ["assert", "assert_eq", "assert_throws"];
+
+/// Keeps what a test prints, to show if it fails.
+#[derive(Default)]
+struct Capture(String);
+
+impl Hooks for Capture {
+    fn print(&mut self, text: &str) -> bool {
+        self.0.push_str(text);
+        self.0.push('\n');
+        true
+    }
+}
+
+/// A test that failed, with what it printed.
+struct Failure {
+    file: String,
+    name: String,
+    error: String,
+    source: Option<String>,
+    printed: String,
+}
+
+/// Runs the tests in `paths`, which are test files or directories searched
+/// for `*_test.lox` files, reporting to `out`. Every test runs its file in
+/// a fresh interpreter and then calls the test function. Returns the
+/// process exit status: 1 when a test failed.
+pub fn run(paths: &[String], opts: &Options, out: &mut dyn Write) -> i32 {
+    let mut files: Vec<PathBuf> = Vec::new();
+    let mut status = 0;
+    for path in paths {
+        if let Err(e) = discover(Path::new(path), true, &mut files) {
+            eprintln!("rlox: cannot read {}: {}", path, e);
+            status = EX_IOERR;
+        }
+    }
+
+    let start = Instant::now();
+    let (mut passed, mut failures) = (0, Vec::new());
+    for file in &files {
+        let path = file.to_string_lossy();
+        let code = match read_source(&path) {
+            Ok(code) => code,
+            Err(e) => {
+                eprintln!("rlox: cannot read {}: {}", path, e);
+                status = status.max(EX_IOERR);
+                continue;
+            }
+        };
+
+        let stmts = match analyze(&code, Resolver::new()) {
+            Ok(stmts) => stmts,
+            Err(errors) => {
+                for err in &errors {
+                    eprintln!("{}: {}", path, err);
+                }
+                status = status.max(EX_DATAERR);
+                continue;
+            }
+        };
+
+        let lines: Vec<&str> = code.lines().collect();
+        let tests = tests(&stmts);
+        let noun = match tests.len() {
+            1 => "test",
+            _ => "tests",
+        };
+        let _ = writeln!(out, "running {} {} in {}", tests.len(), noun, path);
+        for name in tests {
+            let capture = Rc::new(RefCell::new(Capture::default()));
+            let mut i = script_interpreter(opts).hooks(capture.clone());
+            define_asserts(&i);
+
+            let start = Instant::now();
+            let result = run_test(&mut i, &stmts, &name);
+            let time = millis(start.elapsed());
+
+            match result {
+                Ok(()) => {
+                    let _ = writeln!(out, "test {} ... ok ({})", name, time);
+                    passed += 1;
+                }
+                Err(e) => {
+                    let _ = writeln!(out, "test {} ... FAILED ({})", name, time);
+                    let source =
+                        line(&e).map(|l| format!("{:>4} | {}", l, source_line(&lines, l).trim()));
+                    failures.push(Failure {
+                        file: path.to_string(),
+                        name,
+                        error: e.to_string(),
+                        source,
+                        printed: std::mem::take(&mut capture.borrow_mut().0),
+                    });
+                }
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        let _ = writeln!(out, "\nfailures:");
+        for f in &failures {
+            let _ = writeln!(
+                out,
+                "\n---- {} {} ----\n{}",
+                f.file,
+                f.name,
+                f.error.trim_end()
+            );
+            if let Some(source) = &f.source {
+                let _ = writeln!(out, "{}", source);
+            }
+            if !f.printed.is_empty() {
+                let _ = write!(out, "printed:\n{}", f.printed);
+            }
+        }
+        status = status.max(1);
+    }
+
+    let result = match status {
+        0 => "ok",
+        _ => "FAILED",
+    };
+    let _ = writeln!(
+        out,
+        "\ntest result: {}. {} passed; {} failed; finished in {}",
+        result,
+        passed,
+        failures.len(),
+        millis(start.elapsed())
+    );
+    status
+}
+
+/// Adds the test files at `path` to `files`: the path itself when it was
+/// named on the command line, or the `*_test.lox` files below it.
+fn discover(path: &Path, named: bool, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    if !path.is_dir() {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if named || name.ends_with(SUFFIX) {
+            files.push(path.to_path_buf());
+        }
+        return Ok(());
+    }
+
+    let mut entries: Vec<PathBuf> = fs::read_dir(path)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<io::Result<_>>()?;
+    entries.sort();
+    for entry in entries {
+        let hidden = entry
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        if !hidden {
+            discover(&entry, false, files)?;
+        }
+    }
+    Ok(())
+}
+
+/// The names of the test functions declared at the top of a file.
+fn tests(stmts: &[Stmt]) -> Vec<String> {
+    let mut names = Vec::new();
+    for stmt in stmts {
+        let stmt = match stmt {
+            Stmt::Doc(_, inner) => inner,
+            stmt => stmt,
+        };
+        if let Stmt::Function(name, _, _) = stmt {
+            if name.lexeme.starts_with(PREFIX) {
+                names.push(name.lexeme.clone());
+            }
+        }
+    }
+    names
+}
+
+/// Defines the assertion natives, which only tests can call.
+fn define_asserts(i: &Interpreter) {
+    let natives: Vec<Rc<dyn Callable>> = vec![
+        NativeFunction::new("assert", &["condition"], function::assert),
+        NativeFunction::new("assert_eq", &["actual", "expected"], function::assert_eq),
+        NativeFunction::new("assert_throws", &["f"], function::assert_throws),
+    ];
+
+    for native in natives {
+        let _ = i
+            .env
+            .define_builtin(native.name(), Object::Func(native.clone()));
+    }
+}
+
+fn run_test(i: &mut Interpreter, stmts: &[Stmt], name: &str) -> ResultMSG<()> {
+    for stmt in stmts {
+        i.interpret(stmt)?;
+    }
+
+    let f = match i.env.get(name)? {
+        Object::Func(f) => f,
+        _ => {
+            return Err(Error::Runtime(
+                0,
+                "not a function".to_string(),
+                name.to_string(),
+            ))
+        }
+    };
+    let bound = f
+        .signature()
+        .bind(Vec::new(), Vec::new())
+        .map_err(|msg| Error::Runtime(0, msg, name.to_string()))?;
+    f.call(i, &bound).map(|_| ())
+}
+
+fn line(e: &Error) -> Option<u32> {
+    match *e {
+        Error::Lexer(l, _, _)
+        | Error::Parser(l, _, _)
+        | Error::Resolve(l, _, _)
+        | Error::Runtime(l, _, _)
+        | Error::Break(l) => Some(l).filter(|l| *l > 0),
+        Error::Return(l, _) => Some(l as u32).filter(|l| *l > 0),
+    }
+}
+
+fn millis(time: Duration) -> String {
+    format!("{:.2} ms", time.as_secs_f64() * 1000.0)
+}
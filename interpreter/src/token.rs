@@ -7,13 +7,22 @@ pub enum TokenType {
     RIGHT_PAREN,
     LEFT_BRACE,
     RIGHT_BRACE,
+    LEFT_BRACKET,
+    RIGHT_BRACKET,
     COMMA,
     DOT,
     MINUS,
     PLUS,
     SEMICOLON,
+    COLON,
+    QUESTION,
     SLASH,
     STAR,
+    PERCENT,
+    AMPERSAND,
+    PIPE,
+    CARET,
+    TILDE,
 
     // One or two character tokens.
     BANG,
@@ -24,11 +33,35 @@ pub enum TokenType {
     GREATER_EQUAL,
     LESS,
     LESS_EQUAL,
+    STAR_STAR,
+    LESS_LESS,
+    GREATER_GREATER,
+    TILDE_SLASH,
+    QUESTION_QUESTION,
+    QUESTION_DOT,
+    ELLIPSIS,
+    PLUS_PLUS,
+    MINUS_MINUS,
+    PLUS_EQUAL,
+    MINUS_EQUAL,
+    STAR_EQUAL,
+    SLASH_EQUAL,
+    PERCENT_EQUAL,
+    STAR_STAR_EQUAL,
+    TILDE_SLASH_EQUAL,
+    AMPERSAND_EQUAL,
+    PIPE_EQUAL,
+    CARET_EQUAL,
+    LESS_LESS_EQUAL,
+    GREATER_GREATER_EQUAL,
 
     // Literals.
     IDENTIFIER,
     STRING,
     NUMBER,
+    INTERPOLATION,
+    DOC_COMMENT,
+    COMMENT,
 
     // Keywords.
     AND,
@@ -46,12 +79,35 @@ pub enum TokenType {
     THIS,
     TRUE,
     VAR,
+    LET,
+    CONST,
     WHILE,
     BREAK,
 
     EOF,
 }
 
+impl TokenType {
+    /// The binary operator a compound assignment such as `+=` applies.
+    pub fn compound_op(&self) -> Option<TokenType> {
+        match self {
+            TokenType::PLUS_EQUAL => Some(TokenType::PLUS),
+            TokenType::MINUS_EQUAL => Some(TokenType::MINUS),
+            TokenType::STAR_EQUAL => Some(TokenType::STAR),
+            TokenType::SLASH_EQUAL => Some(TokenType::SLASH),
+            TokenType::PERCENT_EQUAL => Some(TokenType::PERCENT),
+            TokenType::STAR_STAR_EQUAL => Some(TokenType::STAR_STAR),
+            TokenType::TILDE_SLASH_EQUAL => Some(TokenType::TILDE_SLASH),
+            TokenType::AMPERSAND_EQUAL => Some(TokenType::AMPERSAND),
+            TokenType::PIPE_EQUAL => Some(TokenType::PIPE),
+            TokenType::CARET_EQUAL => Some(TokenType::CARET),
+            TokenType::LESS_LESS_EQUAL => Some(TokenType::LESS_LESS),
+            TokenType::GREATER_GREATER_EQUAL => Some(TokenType::GREATER_GREATER),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Literal {
     StringLit(String),
@@ -67,6 +123,8 @@ pub struct Token {
     pub lexeme: String,
     pub literal: Literal,
     pub line: u32,
+    /// Chars between the start of the line and the token.
+    pub column: u32,
 }
 
 impl Token {
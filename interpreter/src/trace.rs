@@ -0,0 +1,76 @@
+use std::{io::Write, rc::Rc};
+
+use crate::{
+    env::Env, error::ResultMSG, hooks::Hooks, object::Object, source_line, stmt::Stmt, token::Token,
+};
+
+/// Logs what a program does through the interpreter's hooks: each
+/// statement with its source line, each call with its arguments and
+/// result, and each assignment with the values before and after. Lines are
+/// indented by call depth.
+pub struct Tracer {
+    out: Box<dyn Write>,
+    lines: Vec<String>,
+    /// Only trace inside calls to these functions, or everywhere if empty.
+    functions: Vec<String>,
+    /// The functions being run, innermost last.
+    calls: Vec<String>,
+}
+
+impl Tracer {
+    pub fn new(code: &str, out: Box<dyn Write>, functions: Vec<String>) -> Self {
+        Tracer {
+            out,
+            lines: code.lines().map(|l| l.trim().to_string()).collect(),
+            functions,
+            calls: Vec::new(),
+        }
+    }
+
+    fn tracing(&self) -> bool {
+        self.functions.is_empty() || self.calls.iter().any(|f| self.functions.contains(f))
+    }
+
+    /// Writes a line indented by `depth` calls, if tracing.
+    fn log(&mut self, depth: usize, text: &str) {
+        if self.tracing() {
+            let _ = writeln!(self.out, "{}{}", "  ".repeat(depth), text);
+        }
+    }
+}
+
+impl Hooks for Tracer {
+    fn statement(&mut self, stmt: &Stmt, _env: &Rc<Env>) -> ResultMSG<()> {
+        if let Some(line) = stmt.line() {
+            let text = format!("{}: {}", line, source_line(&self.lines, line));
+            self.log(self.calls.len(), &text);
+        }
+        Ok(())
+    }
+
+    fn assign(&mut self, name: &Token, old: &Object, new: &Object) {
+        let text = format!("{} = {} (was {})", name.lexeme, new.repr(), old.repr());
+        self.log(self.calls.len(), &text);
+    }
+
+    fn enter(&mut self, name: &str, _body: &Stmt, args: &[(String, Object)]) {
+        let args: Vec<String> = args
+            .iter()
+            .map(|(param, value)| format!("{}: {}", param, value.repr()))
+            .collect();
+        let text = format!("call {}({})", name, args.join(", "));
+
+        // the call and its result sit at the caller's depth
+        self.calls.push(name.to_string());
+        self.log(self.calls.len().saturating_sub(1), &text);
+    }
+
+    fn exit(&mut self, name: &str, result: &ResultMSG<Object>) {
+        let text = match result {
+            Ok(value) => format!("{} returned {}", name, value.repr()),
+            Err(e) => format!("{} failed: {}", name, e),
+        };
+        self.log(self.calls.len().saturating_sub(1), &text);
+        self.calls.pop();
+    }
+}
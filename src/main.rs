@@ -1,19 +1,181 @@
-use interpreter::{run_file, run_prompt};
-use std::env;
+use interpreter::{
+    check_files, debug_file, format_files, lint_files, run_file, run_prompt, run_source, serve_dap,
+    serve_lsp, test_files, Options, EX_IOERR, EX_USAGE,
+};
+use std::{env, process};
+
+const USAGE: &str = "\
+Usage: rlox [options] [script | -] [args...]
+       rlox run [options] <script | -> [args...]
+       rlox repl [--compat]
+       rlox check [--json] <script>...
+       rlox fmt [--check] <script | ->...
+       rlox lint [--config <file>] <script>...
+       rlox test [--compat] [file | dir]...
+       rlox lsp
+       rlox dap
+       rlox debug [--compat] <script> [args...]
+
+Commands:
+  run      run a script, reading it from stdin when given `-`
+  repl     start an interactive session (the default without a script)
+  check    scan, parse and resolve scripts without running them
+  fmt      rewrite scripts in the canonical layout
+  lint     warn about suspicious code, with rules set in `.loxlint`
+  test     run the `test_` functions of files, or of `*_test.lox` files in dirs
+  lsp      serve the Language Server Protocol on stdin and stdout
+  dap      serve the Debug Adapter Protocol on stdin and stdout
+  debug    step through a script with breakpoints at an interactive prompt
+
+Options:
+  -e, --eval <code>  run <code> instead of a script
+  --json             report `check` findings as a JSON array
+  --check            make `fmt` list unformatted scripts instead of fixing them
+  --config <file>    read `lint` rules from <file> instead of `.loxlint`
+  --profile          print the time spent per function and line to stderr
+  --folded <file>    write the call stacks of a run to <file> for flame graphs
+  --coverage <file>  print what ran to stderr and write it to <file> as lcov
+  --trace            log statements, calls and assignments to stderr
+  --trace-to <file>  log them to <file> instead
+  --trace-fn <name>  only log inside calls to <name>, which may repeat
+  --compat           use the pre-spec truthiness and logical operators
+  -h, --help         show this message";
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-
-    dbg!(&args);
-
-    if args.len() > 2 {
-        println!("Usage: rlox <script>")
-    } else if args.len() == 2 {
-        run_file(args[1].clone());
-    } else {
-        match run_prompt() {
-            Err(e) => panic!("Run prompt {}", e),
-            Ok(_) => (),
+    let args: Vec<String> = env::args().skip(1).collect();
+    process::exit(rlox(args));
+}
+
+fn usage(msg: &str) -> i32 {
+    eprintln!("rlox: {}\n\n{}", msg, USAGE);
+    EX_USAGE
+}
+
+/// Whether `opts` asks to profile, cover or trace the run.
+fn traced(opts: &Options) -> bool {
+    opts.profile
+        || opts.folded.is_some()
+        || opts.coverage.is_some()
+        || opts.trace
+        || opts.trace_to.is_some()
+        || !opts.trace_functions.is_empty()
+}
+
+fn rlox(mut args: Vec<String>) -> i32 {
+    let command = match args.first().map(String::as_str) {
+        Some("run" | "repl" | "check" | "fmt" | "lint" | "test" | "lsp" | "dap" | "debug") => {
+            Some(args.remove(0))
+        }
+        _ => None,
+    };
+
+    let mut opts = Options::default();
+    let mut eval: Option<String> = None;
+    let mut json = false;
+    let mut check = false;
+    let mut config: Option<String> = None;
+    let mut positional: Vec<String> = Vec::new();
+    // commands that pass the arguments after a script on to it
+    let runs = matches!(command.as_deref(), None | Some("run" | "debug"));
+    // commands that can profile, cover or trace what they run
+    let tools = matches!(command.as_deref(), None | Some("run"));
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--compat" => opts.compat = true,
+            "--json" if command.as_deref() == Some("check") => json = true,
+            "--check" if command.as_deref() == Some("fmt") => check = true,
+            "--config" if command.as_deref() == Some("lint") => match args.next() {
+                Some(path) => config = Some(path),
+                None => return usage("missing file after --config"),
+            },
+            "--profile" if tools => opts.profile = true,
+            "--folded" if tools => match args.next() {
+                Some(path) => opts.folded = Some(path),
+                None => return usage("missing file after --folded"),
+            },
+            "--coverage" if tools => match args.next() {
+                Some(path) => opts.coverage = Some(path),
+                None => return usage("missing file after --coverage"),
+            },
+            "--trace" if tools => opts.trace = true,
+            "--trace-to" if tools => match args.next() {
+                Some(path) => opts.trace_to = Some(path),
+                None => return usage("missing file after --trace-to"),
+            },
+            "--trace-fn" if tools => match args.next() {
+                Some(name) => opts.trace_functions.push(name),
+                None => return usage("missing function after --trace-fn"),
+            },
+            "--profile" | "--folded" | "--coverage" | "--trace" | "--trace-to" | "--trace-fn" => {
+                return usage(&format!("`{}` only applies when running a script", arg))
+            }
+            "-e" | "--eval" => match args.next() {
+                Some(code) => eval = Some(code),
+                None => return usage("missing code after --eval"),
+            },
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return 0;
+            }
+            "--" => positional.extend(args.by_ref()),
+            a if a.starts_with('-') && a != "-" => {
+                return usage(&format!("unknown option `{}`", a))
+            }
+            // everything after the script belongs to the script
+            _ if runs => {
+                positional.push(arg);
+                positional.extend(args.by_ref());
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    match command.as_deref() {
+        Some("repl") if !positional.is_empty() || eval.is_some() => usage("repl takes no script"),
+        Some("check") if positional.is_empty() => usage("check needs at least one script"),
+        Some("check") => check_files(&positional, json),
+        Some("fmt") if positional.is_empty() => usage("fmt needs at least one script"),
+        Some("fmt") => format_files(&positional, check),
+        Some("lint") if positional.is_empty() => usage("lint needs at least one script"),
+        Some("lint") => lint_files(&positional, config.as_deref()),
+        Some("test") if eval.is_some() => usage("test takes no code"),
+        Some("test") if positional.is_empty() => test_files(&[".".to_string()], &opts),
+        Some("test") => test_files(&positional, &opts),
+        Some("lsp") if !positional.is_empty() || eval.is_some() => usage("lsp takes no script"),
+        Some("lsp") => serve_lsp(),
+        Some("dap") if !positional.is_empty() || eval.is_some() => usage("dap takes no script"),
+        Some("dap") => serve_dap(),
+        Some("debug") if positional.is_empty() || eval.is_some() => usage("debug needs a script"),
+        Some("debug") => {
+            let script = positional.remove(0);
+            opts.args = positional;
+            debug_file(&script, &opts)
+        }
+        Some("run") if positional.is_empty() && eval.is_none() => usage("run needs a script"),
+        None if positional.is_empty() && eval.is_none() && traced(&opts) => {
+            usage("--profile, --folded, --coverage and --trace need a script")
+        }
+        None | Some("repl") if positional.is_empty() && eval.is_none() => {
+            match run_prompt(opts.compat) {
+                Ok(()) => 0,
+                Err(e) => {
+                    eprintln!("rlox: {}", e);
+                    EX_IOERR
+                }
+            }
         }
+        _ => match eval {
+            Some(code) => {
+                opts.args = positional;
+                run_source(&code, &opts)
+            }
+            None => {
+                let script = positional.remove(0);
+                opts.args = positional;
+                run_file(&script, &opts)
+            }
+        },
     }
 }